@@ -0,0 +1,543 @@
+use crate::core::{
+    Block, Coord, Game, Grid, State, FRAMES_PER_SECOND, PLAYGROUND_HEIGHT, PLAYGROUND_WIDTH,
+};
+use crate::movegen::{placements, Placement};
+use crate::random::Random;
+use crate::shape::Shape;
+use crate::tetromino::Tetromino;
+use rand::seq::SliceRandom;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+pub const DEFAULT_DEPTH: usize = 2;
+/// How many pieces a game without a human lasts at most, in case the bot
+/// never tops out.
+pub const DEFAULT_PIECES: u32 = 1000;
+
+/// How much each board feature counts towards a placement's score. Features
+/// that make the board worse get negative weights.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weights {
+    pub height: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+    pub wells: f64,
+    pub lines: f64,
+}
+
+impl Default for Weights {
+    /// Close to the weights of Yiyuan Lee's near-perfect player, with deep
+    /// wells slightly penalised.
+    fn default() -> Weights {
+        Weights {
+            height: -0.51,
+            holes: -0.36,
+            bumpiness: -0.18,
+            wells: -0.05,
+            lines: 0.76,
+        }
+    }
+}
+
+/// The board features a placement is judged by.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Features {
+    /// The column heights added up.
+    pub height: u32,
+    /// Empty cells with a filled cell somewhere above them.
+    pub holes: u32,
+    /// The height differences between neighbouring columns added up.
+    pub bumpiness: u32,
+    /// How far columns sit below both their neighbours, added up. Walls count
+    /// as full columns.
+    pub wells: u32,
+    pub lines: u32,
+}
+
+impl Features {
+    pub fn new(grid: &Grid, lines: u32) -> Features {
+        let heights = (0..PLAYGROUND_WIDTH as usize)
+            .map(|x| {
+                grid.iter()
+                    .position(|row| row[x].value != 0)
+                    .map_or(0, |y| PLAYGROUND_HEIGHT as u32 - y as u32)
+            })
+            .collect::<Vec<u32>>();
+
+        let mut holes = 0;
+        for (x, height) in heights.iter().enumerate() {
+            let top = (PLAYGROUND_HEIGHT as u32 - height) as usize;
+            holes += grid[top..].iter().filter(|row| row[x].value == 0).count() as u32;
+        }
+
+        let bumpiness = heights
+            .windows(2)
+            .map(|pair| (pair[0] as i32 - pair[1] as i32).unsigned_abs())
+            .sum();
+
+        let wall = PLAYGROUND_HEIGHT as u32;
+        let wells = (0..heights.len())
+            .map(|x| {
+                let left = if x == 0 { wall } else { heights[x - 1] };
+                let right = heights.get(x + 1).copied().unwrap_or(wall);
+                left.min(right).saturating_sub(heights[x])
+            })
+            .sum();
+
+        Features {
+            height: heights.iter().sum(),
+            holes,
+            bumpiness,
+            wells,
+            lines,
+        }
+    }
+}
+
+impl Weights {
+    pub fn score(&self, features: &Features) -> f64 {
+        self.height * features.height as f64
+            + self.holes * features.holes as f64
+            + self.bumpiness * features.bumpiness as f64
+            + self.wells * features.wells as f64
+            + self.lines * features.lines as f64
+    }
+}
+
+/// Something that plays a game in place of a person.
+pub trait Player {
+    /// Plays the game for one frame. Call it before every `Game::tick`.
+    fn step(&mut self, game: &mut Game) -> Result<(), String>;
+}
+
+/// A player that puts every piece in the placement that scores best.
+#[derive(Clone, Debug)]
+pub struct Bot {
+    pub weights: Weights,
+    /// How many pieces each decision looks at: 1 is only the active piece,
+    /// 2 adds the next one and so on.
+    pub depth: usize,
+    /// The most pieces it places per second, or `None` to play as fast as
+    /// the game lets it.
+    pub pieces_per_second: Option<f64>,
+    /// The piece count the last placement was made at.
+    placed_at: Option<u32>,
+    /// The first frame the next placement may be made on.
+    ready_at: u64,
+}
+
+impl Default for Bot {
+    fn default() -> Bot {
+        Bot::new(DEFAULT_DEPTH, None)
+    }
+}
+
+impl Bot {
+    pub fn new(depth: usize, pieces_per_second: Option<f64>) -> Bot {
+        Bot {
+            weights: Weights::default(),
+            depth: depth.max(1),
+            pieces_per_second,
+            placed_at: None,
+            ready_at: 0,
+        }
+    }
+
+    /// The placement it would pick for the active piece, if it has one.
+    pub fn best_placement(&self, game: &Game) -> Option<Placement> {
+        self.best_placement_on(game.board(), game.piece(), &game.queue())
+    }
+
+    /// The placement it would pick for `piece` on `grid`, with `queue` the
+    /// pieces coming up after it.
+    pub fn best_placement_on(
+        &self,
+        grid: &Grid,
+        piece: &Tetromino,
+        queue: &[Shape],
+    ) -> Option<Placement> {
+        let lookahead = &queue[..queue.len().min(self.depth - 1)];
+        placements(piece)
+            .into_iter()
+            .map(|placement| {
+                let value = self.value(grid, piece.scale, &placement, lookahead, 0);
+                (value, placement)
+            })
+            .max_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, placement)| placement)
+    }
+
+    /// The score of the board after filling in the cells, without looking ahead.
+    pub fn evaluate(&self, grid: &Grid, scale: i32, cells: &[Coord]) -> f64 {
+        let (grid, lines) = lock(grid, scale, cells);
+        self.weights.score(&Features::new(&grid, lines))
+    }
+
+    /// The score of the board after the placement and the best placements of
+    /// the upcoming pieces. Placements that end the game are worth nothing.
+    fn value(
+        &self,
+        grid: &Grid,
+        scale: i32,
+        placement: &Placement,
+        upcoming: &[Shape],
+        lines: u32,
+    ) -> f64 {
+        let (grid, cleared) = match land(grid, scale, placement) {
+            Some(landed) => landed,
+            None => return f64::NEG_INFINITY,
+        };
+        let lines = lines + cleared;
+        match upcoming.split_first() {
+            None => self.weights.score(&Features::new(&grid, lines)),
+            Some((&shape, rest)) => {
+                let rotation = shape.get_possible_rotations()[0];
+                placements(&Tetromino::from_shape(grid, scale, shape, rotation))
+                    .iter()
+                    .map(|next| self.value(&grid, scale, next, rest, lines))
+                    .fold(f64::NEG_INFINITY, f64::max)
+            }
+        }
+    }
+}
+
+impl Player for Bot {
+    /// Once a new piece is in play and the speed limit allows, moves it to
+    /// the best placement.
+    fn step(&mut self, game: &mut Game) -> Result<(), String> {
+        if game.state() != State::Playing
+            || self.placed_at == Some(game.pieces)
+            || game.frame < self.ready_at
+        {
+            return Ok(());
+        }
+        if let Some(placement) = self.best_placement(game) {
+            for action in placement.inputs {
+                game.apply(action).ok();
+            }
+        }
+        self.placed_at = Some(game.pieces);
+        if let Some(pps) = self.pieces_per_second {
+            self.ready_at = game.frame + (FRAMES_PER_SECOND as f64 / pps) as u64;
+        }
+        Ok(())
+    }
+}
+
+/// What a `ThreadedBot` asks its thread: the piece count, the board, the
+/// active piece and the pieces after it.
+type Request = (u32, Grid, Tetromino, Vec<Shape>);
+
+/// What a `ThreadedBot`'s thread answers: the piece count it was asked at
+/// and the cells it picked.
+type Answer = (u32, Option<Vec<Coord>>);
+
+/// A `Bot` thinking on its own thread, so the game never waits on it. It
+/// asks for a placement once a new piece is in play and the speed limit
+/// allows, and makes it as soon as the answer comes back.
+pub struct ThreadedBot {
+    requests: Sender<Request>,
+    answers: Receiver<Answer>,
+    pieces_per_second: Option<f64>,
+    /// The piece count the last placement was asked for at.
+    asked_at: Option<u32>,
+    /// The first frame the next placement may be asked for on.
+    ready_at: u64,
+}
+
+impl ThreadedBot {
+    /// Starts the bot's thread, which ends once this is dropped.
+    pub fn spawn(bot: Bot) -> ThreadedBot {
+        let (requests, inbox) = mpsc::channel::<Request>();
+        let (outbox, answers) = mpsc::channel();
+        let pieces_per_second = bot.pieces_per_second;
+        thread::spawn(move || {
+            for (pieces, grid, piece, queue) in inbox {
+                let placement = bot.best_placement_on(&grid, &piece, &queue);
+                let cells = placement.map(|placement| placement.cells);
+                if outbox.send((pieces, cells)).is_err() {
+                    break;
+                }
+            }
+        });
+        ThreadedBot {
+            requests,
+            answers,
+            pieces_per_second,
+            asked_at: None,
+            ready_at: 0,
+        }
+    }
+}
+
+impl Player for ThreadedBot {
+    fn step(&mut self, game: &mut Game) -> Result<(), String> {
+        if game.state() != State::Playing {
+            return Ok(());
+        }
+        if self.asked_at != Some(game.pieces) && game.frame >= self.ready_at {
+            let request = (
+                game.pieces,
+                *game.board(),
+                game.piece().clone(),
+                game.queue(),
+            );
+            self.requests
+                .send(request)
+                .map_err(|_| "The bot's thread stopped.".to_string())?;
+            self.asked_at = Some(game.pieces);
+            if let Some(pps) = self.pieces_per_second {
+                self.ready_at = game.frame + (FRAMES_PER_SECOND as f64 / pps) as u64;
+            }
+        }
+        while let Ok(answer) = self.answers.try_recv() {
+            play_answer(game, answer);
+        }
+        Ok(())
+    }
+}
+
+/// Brings the piece to the cells the bot picked. The piece may have fallen
+/// while the bot was thinking, so the way there is found again from where it
+/// is now, and the answer is dropped if there no longer is one.
+fn play_answer(game: &mut Game, (pieces, cells): Answer) {
+    // Answers for pieces already placed are of no use any more.
+    if let (true, Some(cells)) = (pieces == game.pieces, cells) {
+        let placement = placements(game.piece())
+            .into_iter()
+            .find(|placement| placement.cells == cells);
+        for action in placement.map_or(vec![], |placement| placement.inputs) {
+            game.apply(action).ok();
+        }
+    }
+}
+
+/// A baseline that puts every piece in a random reachable placement.
+#[derive(Clone, Debug)]
+pub struct RandomBot {
+    rng: Random,
+    placed_at: Option<u32>,
+}
+
+impl RandomBot {
+    pub fn new(seed: u64) -> RandomBot {
+        RandomBot {
+            rng: Random::new(seed),
+            placed_at: None,
+        }
+    }
+}
+
+impl Player for RandomBot {
+    fn step(&mut self, game: &mut Game) -> Result<(), String> {
+        if game.state() != State::Playing || self.placed_at == Some(game.pieces) {
+            return Ok(());
+        }
+        if let Some(placement) = placements(game.piece()).choose(&mut self.rng) {
+            for action in &placement.inputs {
+                game.apply(*action).ok();
+            }
+        }
+        self.placed_at = Some(game.pieces);
+        Ok(())
+    }
+}
+
+/// Lets the player play until the game is over or `pieces` pieces are placed.
+pub fn play(game: &mut Game, player: &mut dyn Player, pieces: u32) -> Result<(), String> {
+    while !game.is_over() && game.pieces < pieces {
+        player.step(game)?;
+        game.tick();
+    }
+    Ok(())
+}
+
+/// The grid with the placement locked in and full rows cleared, with the
+/// number of lines cleared, or `None` if locking there ends the game.
+fn land(grid: &Grid, scale: i32, placement: &Placement) -> Option<(Grid, u32)> {
+    if placement.topleft.y <= 0 {
+        return None;
+    }
+    Some(lock(grid, scale, &placement.cells))
+}
+
+/// The grid with the cells filled in and full rows cleared, with the number
+/// of lines cleared.
+pub fn lock(grid: &Grid, scale: i32, cells: &[Coord]) -> (Grid, u32) {
+    let mut landed = *grid;
+    for cell in cells {
+        landed[cell.y as usize][cell.x as usize].value = 1;
+    }
+    let mut cleared = 0;
+    for y in 0..landed.len() {
+        if landed[y].iter().all(|block| block.value != 0) {
+            landed[..y + 1].rotate_right(1);
+            landed[0] = [Block::new(0, None); PLAYGROUND_WIDTH as usize];
+            cleared += 1;
+        }
+    }
+    (landed, cleared / scale as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::{Mode, Settings};
+
+    fn grid(rows: &[&str]) -> Grid {
+        let mut grid = Game::create_grid();
+        let top = PLAYGROUND_HEIGHT as usize - rows.len();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                if cell == '#' {
+                    grid[top + y][x] = Block::new(1, None);
+                }
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn features_empty() {
+        assert_eq!(Features::new(&Game::create_grid(), 0), Features::default());
+    }
+
+    #[test]
+    fn features() {
+        let grid = grid(&["#.........", "##.#......", "#.##....##"]);
+        let features = Features::new(&grid, 2);
+        assert_eq!(features.height, 3 + 2 + 1 + 2 + 1 + 1);
+        assert_eq!(features.holes, 1);
+        assert_eq!(features.bumpiness, 1 + 1 + 1 + 2 + 1);
+        // Only column 2 sits below both its neighbours.
+        assert_eq!(features.wells, 1);
+        assert_eq!(features.lines, 2);
+    }
+
+    #[test]
+    fn land_clears_lines() {
+        let grid = grid(&["###.######", "###.######"]);
+        let start = Tetromino::from_shape(grid, 1, Shape::I, 8738);
+        let placement = placements(&start)
+            .into_iter()
+            .find(|placement| placement.cells.contains(&Coord { y: 15, x: 3 }))
+            .unwrap();
+        let (landed, cleared) = land(&grid, 1, &placement).unwrap();
+        assert_eq!(cleared, 2);
+        assert_eq!(Features::new(&landed, 0).height, 2);
+    }
+
+    #[test]
+    fn prefers_clearing_lines() {
+        let mut game = Game::with_seed(Settings::default(), 1);
+        let board = grid(&["#########."]);
+        let piece = Tetromino::from_shape(board, 1, Shape::I, 8738);
+        game.set_position(board, piece, vec![], None);
+        let placement = Bot::new(1, None).best_placement(&game).unwrap();
+        assert!(placement.cells.contains(&Coord { y: 15, x: 9 }));
+    }
+
+    #[test]
+    fn plays_without_topping_out() {
+        let mut game = Game::with_seed(Settings::default(), 7);
+        play(&mut game, &mut Bot::new(1, None), 200).unwrap();
+        assert!(!game.is_over());
+        assert_eq!(game.pieces, 200);
+        assert!(game.lines > 60);
+    }
+
+    #[test]
+    fn lookahead_plays_master() {
+        let settings = Settings {
+            mode: Mode::Master,
+            ..Settings::default()
+        };
+        let mut game = Game::with_seed(settings, 3);
+        play(&mut game, &mut Bot::default(), 50).unwrap();
+        assert_eq!(game.pieces, 50);
+    }
+
+    #[test]
+    fn random_bot() {
+        let mut game = Game::with_seed(Settings::default(), 1);
+        let mut bot = RandomBot::new(1);
+        play(&mut game, &mut bot, u32::MAX).unwrap();
+        assert!(game.pieces > 5);
+        assert!(game.frame < 40 * game.pieces as u64);
+    }
+
+    #[test]
+    fn speed_limit() {
+        let mut game = Game::with_seed(Settings::default(), 1);
+        let mut bot = Bot::new(1, Some(0.5));
+        while game.frame < 10 * FRAMES_PER_SECOND as u64 {
+            bot.step(&mut game).unwrap();
+            game.tick();
+        }
+        assert!((4..=5).contains(&game.pieces));
+    }
+
+    #[test]
+    fn threaded_bot_plays_from_where_the_piece_fell() {
+        use crate::event::Event;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut game = Game::with_seed(Settings::default(), 1);
+        play(&mut game, &mut Bot::new(1, None), 20).unwrap();
+        let locked = Rc::new(RefCell::new(None));
+        let sink = locked.clone();
+        game.subscribe(move |event| {
+            if let Event::Locked { cells, .. } = event {
+                let mut cells = cells.clone();
+                cells.sort_by_key(|cell| (cell.y, cell.x));
+                *sink.borrow_mut() = Some(cells);
+            }
+        });
+
+        let bot = ThreadedBot::spawn(Bot::new(1, None));
+        for _ in 0..40 {
+            let request = (
+                game.pieces,
+                *game.board(),
+                game.piece().clone(),
+                game.queue(),
+            );
+            bot.requests.send(request).unwrap();
+            let answer = bot.answers.recv().unwrap();
+            // The piece falls a few rows while the bot is thinking, enough
+            // for the way there to change now and then.
+            let row = game.piece().topleft.y;
+            while game.piece().topleft.y < row + 6 && game.piece().clone().move_down().is_ok() {
+                game.tick();
+            }
+            let cells = answer.1.clone();
+            play_answer(&mut game, answer);
+            while locked.borrow().is_none() {
+                game.tick();
+            }
+            assert_eq!(locked.borrow_mut().take(), cells);
+            while game.state() != State::Playing {
+                game.tick();
+            }
+        }
+    }
+
+    #[test]
+    fn threaded_bot() {
+        let mut game = Game::with_seed(Settings::default(), 1);
+        let mut bot = ThreadedBot::spawn(Bot::new(1, Some(30.0)));
+        while game.pieces < 20 && !game.is_over() {
+            bot.step(&mut game).unwrap();
+            game.tick();
+            // Gives the bot's thread time to answer, as real frames would.
+            thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert_eq!(game.pieces, 20);
+        // It stacks as well as the bot does, not piling up in the middle.
+        assert!(game.board()[..8]
+            .iter()
+            .flatten()
+            .all(|block| block.value == 0));
+    }
+}
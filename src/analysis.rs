@@ -0,0 +1,304 @@
+use crate::ai::{self, Bot, Features};
+use crate::core::{Coord, Direction, Grid};
+use crate::event::Event;
+use crate::movegen::{placements, Placement};
+use crate::replay::Replay;
+use crate::shape::Shape;
+use crate::tetromino::Tetromino;
+use std::fmt;
+
+/// How many mistakes the report lists.
+const MISTAKES: usize = 5;
+/// Losses below this are rounding, not mistakes.
+const TOLERANCE: f64 = 0.01;
+
+/// One placement of a game next to the one the AI would have made.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    /// The piece number, counting from 1.
+    pub piece: u32,
+    /// The frame the piece locked on.
+    pub frame: u64,
+    /// The board before the piece locked.
+    pub board: Grid,
+    pub shape: Shape,
+    /// Where the player put the piece.
+    pub player: Vec<Coord>,
+    /// Where the AI would have put it, if it could be placed at all.
+    pub best: Option<Placement>,
+    /// How the AI scores the board the player left.
+    pub player_score: f64,
+    /// How the AI scores the board it would have left.
+    pub best_score: f64,
+    /// Holes the player's placement added to the board.
+    pub holes_created: i32,
+    /// Whether a line-clearing T-spin was there to be had and not taken.
+    pub missed_t_spin: bool,
+}
+
+impl Step {
+    /// How much worse the player's placement scores than the AI's.
+    pub fn loss(&self) -> f64 {
+        (self.best_score - self.player_score).max(0.0)
+    }
+
+    pub fn is_mistake(&self) -> bool {
+        self.loss() > TOLERANCE || self.missed_t_spin
+    }
+
+    fn notes(&self) -> Vec<String> {
+        let mut notes = vec![];
+        if self.holes_created > 0 {
+            notes.push(format!("{} holes created", self.holes_created));
+        }
+        if self.missed_t_spin {
+            notes.push("missed T-spin".to_string());
+        }
+        notes
+    }
+}
+
+/// Every placement of a replayed game, compared against the AI.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Review {
+    pub steps: Vec<Step>,
+}
+
+impl Review {
+    pub fn new(replay: &Replay) -> Review {
+        let bot = Bot::new(1, None);
+        let scale = replay.settings.scale();
+        let mut game = replay.new_game();
+        let mut steps: Vec<Step> = vec![];
+        // The locked blocks before the active piece locks.
+        let mut board = *game.board();
+        let mut pieces = game.pieces;
+        loop {
+            let advanced = replay.step(&mut game);
+            let events = game.drain_events();
+            let t_spin = events.iter().any(
+                |event| matches!(event, Event::LinesCleared { clear, .. } if clear.is_t_spin()),
+            );
+            for event in &events {
+                if let Event::Locked { shape, cells, .. } = event {
+                    steps.push(Step {
+                        piece: steps.len() as u32 + 1,
+                        frame: game.frame,
+                        ..review(&bot, &board, scale, *shape, cells, t_spin)
+                    });
+                }
+            }
+            if game.pieces != pieces {
+                // Undone placements do not count.
+                steps.truncate(game.pieces as usize);
+                board = *game.board();
+                pieces = game.pieces;
+            }
+            if !advanced {
+                break;
+            }
+        }
+        Review { steps }
+    }
+
+    /// The worst placements, worst first.
+    pub fn mistakes(&self, count: usize) -> Vec<&Step> {
+        let mut mistakes = self
+            .steps
+            .iter()
+            .filter(|step| step.is_mistake())
+            .collect::<Vec<&Step>>();
+        mistakes.sort_by(|a, b| b.loss().total_cmp(&a.loss()));
+        mistakes.truncate(count);
+        mistakes
+    }
+
+    pub fn average_loss(&self) -> f64 {
+        if self.steps.is_empty() {
+            return 0.0;
+        }
+        self.steps.iter().map(Step::loss).sum::<f64>() / self.steps.len() as f64
+    }
+}
+
+impl fmt::Display for Review {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} placements, average loss {:.2}",
+            self.steps.len(),
+            self.average_loss()
+        )?;
+        writeln!(
+            f,
+            "{:>5} {:>5} {:>8} {:>8} {:>6}",
+            "piece", "shape", "player", "ai", "loss"
+        )?;
+        for step in &self.steps {
+            write!(
+                f,
+                "{:>5} {:>5} {:>8.2} {:>8.2} {:>6.2}",
+                step.piece,
+                format!("{:?}", step.shape),
+                step.player_score,
+                step.best_score,
+                step.loss()
+            )?;
+            let notes = step.notes();
+            if !notes.is_empty() {
+                write!(f, " {}", notes.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "biggest mistakes:")?;
+        let mistakes = self.mistakes(MISTAKES);
+        if mistakes.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for step in mistakes {
+            write!(
+                f,
+                "  piece {} ({:?}): loss {:.2}",
+                step.piece,
+                step.shape,
+                step.loss()
+            )?;
+            for note in step.notes() {
+                write!(f, ", {}", note)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the placement against the AI's. The piece number and frame are
+/// left for the caller to fill in.
+fn review(
+    bot: &Bot,
+    board: &Grid,
+    scale: i32,
+    shape: Shape,
+    cells: &[Coord],
+    t_spin: bool,
+) -> Step {
+    let mut player = cells.to_vec();
+    player.sort_by_key(|cell| (cell.y, cell.x));
+    let player_score = bot.evaluate(board, scale, &player);
+
+    let piece = Tetromino::from_shape(*board, scale, shape, shape.get_possible_rotations()[0]);
+    let best = bot.best_placement_on(board, &piece, &[]);
+    let best_score = best
+        .as_ref()
+        .map_or(player_score, |best| bot.evaluate(board, scale, &best.cells));
+
+    let holes = |grid: &Grid| Features::new(grid, 0).holes as i32;
+    let (after, _) = ai::lock(board, scale, &player);
+
+    Step {
+        piece: 0,
+        frame: 0,
+        board: *board,
+        shape,
+        player,
+        best,
+        player_score,
+        best_score,
+        holes_created: holes(&after) - holes(board),
+        missed_t_spin: shape == Shape::T && !t_spin && t_spin_available(&piece),
+    }
+}
+
+/// Whether the T piece has a placement that clears lines as a T-spin: one
+/// with three corners filled that it could have rotated into.
+fn t_spin_available(piece: &Tetromino) -> bool {
+    placements(piece).iter().any(|placement| {
+        let mut spun = piece.clone();
+        spun.topleft = placement.topleft;
+        spun.current_rotation = placement.rotation;
+        // Rotations are in place, so any rotation out also works back in.
+        let rotatable = [Direction::Left, Direction::Right]
+            .iter()
+            .any(|&direction| spun.clone().rotate(direction).is_ok());
+        rotatable
+            && spun.filled_corners() >= 3
+            && ai::lock(&piece.grid, piece.scale, &placement.cells).1 > 0
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::Player;
+    use crate::core::{Action, Block, Game, PLAYGROUND_HEIGHT};
+    use crate::mode::Settings;
+
+    fn grid(rows: &[&str]) -> Grid {
+        let mut grid = Game::create_grid();
+        let top = PLAYGROUND_HEIGHT as usize - rows.len();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                if cell == '#' {
+                    grid[top + y][x] = Block::new(1, None);
+                }
+            }
+        }
+        grid
+    }
+
+    /// A replay of the player placing `pieces` pieces.
+    fn record(player: &mut dyn Player, pieces: u32) -> Replay {
+        let mut game = Game::with_seed(Settings::default(), 3);
+        ai::play(&mut game, player, pieces).unwrap();
+        Replay::record(&game)
+    }
+
+    /// Drops every piece straight down where it spawns.
+    struct Dropper;
+
+    impl Player for Dropper {
+        fn step(&mut self, game: &mut Game) -> Result<(), String> {
+            game.apply(Action::HardDrop).ok();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn bot_makes_no_mistakes() {
+        let review = Review::new(&record(&mut Bot::new(1, None), 20));
+        assert_eq!(review.steps.len(), 20);
+        assert_eq!(review.steps[19].piece, 20);
+        assert!(review.average_loss() < 0.01);
+        for step in &review.steps {
+            assert_eq!(step.best.as_ref().unwrap().cells, step.player);
+        }
+    }
+
+    #[test]
+    fn stacking_in_the_middle() {
+        let review = Review::new(&record(&mut Dropper, 6));
+        assert_eq!(review.steps.len(), 6);
+        assert!(review.average_loss() > 0.0);
+        let mistakes = review.mistakes(3);
+        assert_eq!(mistakes.len(), 3);
+        assert!(mistakes[0].loss() >= mistakes[1].loss());
+        assert!(review.steps.iter().any(|step| step.holes_created > 0));
+
+        let report = review.to_string();
+        assert!(report.starts_with("6 placements, average loss"));
+        assert!(report.contains("biggest mistakes:\n  piece "));
+    }
+
+    #[test]
+    fn t_spins() {
+        let slot = grid(&["##........", "#...######", "##.#######"]);
+        let open = grid(&["##........", "#...#####.", "##.######."]);
+        let flat = grid(&["##########", "##########"]);
+        let t =
+            |grid| Tetromino::from_shape(grid, 1, Shape::T, Shape::T.get_possible_rotations()[0]);
+        assert!(t_spin_available(&t(slot)));
+        assert!(!t_spin_available(&t(open)));
+        assert!(!t_spin_available(&t(flat)));
+        assert!(!t_spin_available(&t(Game::create_grid())));
+    }
+}
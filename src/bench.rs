@@ -0,0 +1,364 @@
+use crate::ai::{self, Bot, Player, RandomBot};
+use crate::core::{Game, FRAMES_PER_SECOND};
+use crate::mode::{Settings, MASTER_MAX_LEVEL};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use strum_macros::{Display, EnumString};
+
+/// Which bot plays the games.
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+#[strum(serialize_all = "lowercase")]
+pub enum BotKind {
+    /// `ai::Bot`, at the configured depth.
+    Heuristic,
+    /// `ai::RandomBot`, as a baseline.
+    Random,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Config {
+    pub settings: Settings,
+    pub bot: BotKind,
+    pub depth: usize,
+    pub games: u64,
+    /// The seed of the first game; the others count up from it.
+    pub seed: u64,
+    /// Games stop after this many pieces if the bot has not topped out.
+    pub pieces: u32,
+    pub threads: usize,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            settings: Settings::default(),
+            bot: BotKind::Heuristic,
+            depth: ai::DEFAULT_DEPTH,
+            games: 10,
+            seed: 0,
+            pieces: ai::DEFAULT_PIECES,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+/// Why a game stopped.
+#[derive(Clone, Copy, Debug, Display, Eq, Ord, PartialEq, PartialOrd)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Cause {
+    ToppedOut,
+    /// Master mode ended at the last level.
+    Completed,
+    PieceLimit,
+}
+
+/// The result of one game.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outcome {
+    pub seed: u64,
+    pub score: u64,
+    pub lines: u32,
+    pub pieces: u32,
+    pub frames: u64,
+    pub cause: Cause,
+}
+
+impl Outcome {
+    pub fn pieces_per_second(&self) -> f64 {
+        if self.frames == 0 {
+            return 0.0;
+        }
+        self.pieces as f64 * FRAMES_PER_SECOND as f64 / self.frames as f64
+    }
+}
+
+/// Plays one game with the configured bot.
+pub fn play(config: &Config, seed: u64) -> Outcome {
+    let mut game = Game::with_seed(config.settings, seed);
+    let mut player: Box<dyn Player> = match config.bot {
+        BotKind::Heuristic => Box::new(Bot::new(config.depth, None)),
+        BotKind::Random => Box::new(RandomBot::new(seed)),
+    };
+    // The built-in bots never fail.
+    ai::play(&mut game, player.as_mut(), config.pieces).ok();
+
+    let cause = if !game.is_over() {
+        Cause::PieceLimit
+    } else if game.level == MASTER_MAX_LEVEL {
+        Cause::Completed
+    } else {
+        Cause::ToppedOut
+    };
+    Outcome {
+        seed,
+        score: game.score(),
+        lines: game.lines,
+        pieces: game.pieces,
+        frames: game.frame,
+        cause,
+    }
+}
+
+/// Plays all the games, spread over the configured number of threads.
+pub fn run(config: &Config) -> Report {
+    let next = AtomicU64::new(0);
+    let outcomes = Mutex::new(vec![]);
+    std::thread::scope(|scope| {
+        for _ in 0..config.threads.max(1) {
+            scope.spawn(|| loop {
+                let game = next.fetch_add(1, Ordering::Relaxed);
+                if game >= config.games {
+                    break;
+                }
+                let outcome = play(config, config.seed.wrapping_add(game));
+                outcomes.lock().unwrap().push(outcome);
+            });
+        }
+    });
+    let mut outcomes = outcomes.into_inner().unwrap();
+    outcomes.sort_by_key(|outcome| outcome.seed.wrapping_sub(config.seed));
+    Report {
+        config: *config,
+        outcomes,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub config: Config,
+    /// In seed order.
+    pub outcomes: Vec<Outcome>,
+}
+
+/// The mean and median of a statistic over all games.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Average {
+    pub mean: f64,
+    pub median: f64,
+}
+
+impl Average {
+    fn new(mut values: Vec<f64>) -> Average {
+        if values.is_empty() {
+            return Average {
+                mean: 0.0,
+                median: 0.0,
+            };
+        }
+        values.sort_by(f64::total_cmp);
+        // The two middle values are the same one for odd lengths.
+        let len = values.len();
+        let median = (values[(len - 1) / 2] + values[len / 2]) / 2.0;
+        Average {
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            median,
+        }
+    }
+}
+
+impl Report {
+    pub fn score(&self) -> Average {
+        self.average(|outcome| outcome.score as f64)
+    }
+
+    pub fn lines(&self) -> Average {
+        self.average(|outcome| outcome.lines as f64)
+    }
+
+    pub fn pieces(&self) -> Average {
+        self.average(|outcome| outcome.pieces as f64)
+    }
+
+    pub fn pieces_per_second(&self) -> Average {
+        self.average(Outcome::pieces_per_second)
+    }
+
+    /// How many games stopped for each cause.
+    pub fn causes(&self) -> BTreeMap<Cause, usize> {
+        let mut causes = BTreeMap::new();
+        for outcome in &self.outcomes {
+            *causes.entry(outcome.cause).or_insert(0) += 1;
+        }
+        causes
+    }
+
+    fn average(&self, statistic: impl Fn(&Outcome) -> f64) -> Average {
+        Average::new(self.outcomes.iter().map(statistic).collect())
+    }
+
+    fn statistics(&self) -> [(&'static str, Average); 4] {
+        [
+            ("score", self.score()),
+            ("lines", self.lines()),
+            ("pieces", self.pieces()),
+            ("pps", self.pieces_per_second()),
+        ]
+    }
+
+    pub fn to_json(&self) -> String {
+        let config = &self.config;
+        let causes = self
+            .causes()
+            .iter()
+            .map(|(cause, count)| (cause.to_string(), json!(count)))
+            .collect::<Map<String, Value>>();
+        let mut json = json!({
+            "games": self.outcomes.len(),
+            "bot": config.bot.to_string(),
+            "depth": config.depth,
+            "mode": config.settings.mode.to_string(),
+            "seed": config.seed,
+            "pieces_limit": config.pieces,
+            "causes": causes,
+        });
+        for (name, average) in self.statistics().iter() {
+            json[*name] = json!({"mean": average.mean, "median": average.median});
+        }
+        json.to_string()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let config = &self.config;
+        write!(f, "{} games, {} bot", self.outcomes.len(), config.bot)?;
+        if config.bot == BotKind::Heuristic {
+            write!(f, " (depth {})", config.depth)?;
+        }
+        writeln!(f, ", {} mode", config.settings.mode)?;
+        writeln!(f, "{:<8}{:>12}{:>12}", "", "mean", "median")?;
+        for (name, average) in self.statistics().iter() {
+            writeln!(
+                f,
+                "{:<8}{:>12.2}{:>12.2}",
+                name, average.mean, average.median
+            )?;
+        }
+        let causes = self
+            .causes()
+            .iter()
+            .map(|(cause, count)| format!("{} {}", cause, count))
+            .collect::<Vec<String>>();
+        writeln!(f, "causes: {}", causes.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::Mode;
+    use rstest::rstest;
+
+    fn config(bot: BotKind, threads: usize) -> Config {
+        Config {
+            bot,
+            depth: 1,
+            games: 4,
+            seed: 5,
+            pieces: 30,
+            threads,
+            ..Config::default()
+        }
+    }
+
+    #[rstest(
+        values,
+        mean,
+        median,
+        case(vec![], 0.0, 0.0),
+        case(vec![3.0, 1.0, 2.0], 2.0, 2.0),
+        case(vec![4.0, 1.0, 1.0, 10.0], 4.0, 2.5)
+    )]
+    fn average(values: Vec<f64>, mean: f64, median: f64) {
+        assert_eq!(Average::new(values), Average { mean, median });
+    }
+
+    #[test]
+    fn threads_do_not_change_results() {
+        let report = run(&config(BotKind::Heuristic, 3));
+        assert_eq!(
+            report.outcomes,
+            run(&config(BotKind::Heuristic, 1)).outcomes
+        );
+        let seeds = report
+            .outcomes
+            .iter()
+            .map(|outcome| outcome.seed)
+            .collect::<Vec<u64>>();
+        assert_eq!(seeds, vec![5, 6, 7, 8]);
+        assert_eq!(report.causes()[&Cause::PieceLimit], 4);
+    }
+
+    #[test]
+    fn random_bot_tops_out() {
+        let config = Config {
+            pieces: 1000,
+            ..config(BotKind::Random, 2)
+        };
+        let report = run(&config);
+        assert_eq!(report.causes()[&Cause::ToppedOut], 4);
+        assert!(report.pieces().mean < 100.0);
+    }
+
+    #[test]
+    fn play_master() {
+        let config = Config {
+            settings: Settings {
+                mode: Mode::Master,
+                ..Settings::default()
+            },
+            ..config(BotKind::Heuristic, 1)
+        };
+        let outcome = play(&config, 1);
+        assert_eq!(outcome.pieces, 30);
+        assert!(outcome.pieces_per_second() > 0.5);
+    }
+
+    #[test]
+    fn text_and_json() {
+        let outcome = |seed, score, cause| Outcome {
+            seed,
+            score,
+            lines: 2,
+            pieces: 10,
+            frames: 300,
+            cause,
+        };
+        let report = Report {
+            config: config(BotKind::Heuristic, 1),
+            outcomes: vec![
+                outcome(5, 10, Cause::PieceLimit),
+                outcome(6, 30, Cause::ToppedOut),
+            ],
+        };
+        assert_eq!(
+            report.to_string(),
+            "2 games, heuristic bot (depth 1), classic mode\n\
+             \x20               mean      median\n\
+             score          20.00       20.00\n\
+             lines           2.00        2.00\n\
+             pieces         10.00       10.00\n\
+             pps             2.00        2.00\n\
+             causes: topped-out 1, piece-limit 1\n"
+        );
+        assert_eq!(
+            serde_json::from_str::<Value>(&report.to_json()).unwrap(),
+            json!({
+                "games": 2,
+                "bot": "heuristic",
+                "depth": 1,
+                "mode": "classic",
+                "seed": 5,
+                "pieces_limit": 30,
+                "score": {"mean": 20.0, "median": 20.0},
+                "lines": {"mean": 2.0, "median": 2.0},
+                "pieces": {"mean": 10.0, "median": 10.0},
+                "pps": {"mean": 2.0, "median": 2.0},
+                "causes": {"topped-out": 1, "piece-limit": 1},
+            })
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use crate::core::{Coord, Game, FRAMES_PER_SECOND, PLAYGROUND_HEIGHT, PLAYGROUND_WIDTH};
+use crate::replay::Replay;
+use crate::ui::Color;
+use std::fmt::Write;
+
+const WIDTH: usize = 40;
+const HEIGHT: usize = PLAYGROUND_HEIGHT as usize + 3;
+const BOARD_WIDTH: usize = PLAYGROUND_WIDTH as usize * 2;
+
+/// Renders a replay as an asciicast v2 recording, one event per frame that
+/// changes what is on screen.
+pub fn export(replay: &Replay) -> String {
+    let mut cast = format!(
+        "{{\"version\": 2, \"width\": {}, \"height\": {}}}\n",
+        WIDTH, HEIGHT
+    );
+    let mut game = replay.new_game();
+    let mut previous = String::new();
+    let mut playing = true;
+    while playing {
+        let screen = render(&game);
+        if screen != previous {
+            let clear = if previous.is_empty() { "\x1b[2J" } else { "" };
+            let data = format!("{}\x1b[H{}", clear, screen);
+            let time = game.frame as f64 / FRAMES_PER_SECOND as f64;
+            writeln!(cast, "[{:.6}, \"o\", \"{}\"]", time, escape(&data)).unwrap();
+            previous = screen;
+        }
+        playing = replay.step(&mut game);
+    }
+    cast
+}
+
+/// Draws the playfield, the next and held pieces and the score the same way `ui` does,
+/// as ANSI-colored text.
+pub fn render(game: &Game) -> String {
+    let mut canvas = Canvas::new();
+
+    canvas.text(0, 0, &format!("┌{}┐", "─".repeat(BOARD_WIDTH)));
+    for y in 1..=PLAYGROUND_HEIGHT as usize {
+        canvas.text(y, 0, "│");
+        canvas.text(y, BOARD_WIDTH + 1, "│");
+    }
+    canvas.text(
+        PLAYGROUND_HEIGHT as usize + 1,
+        0,
+        &format!("└{}┘", "─".repeat(BOARD_WIDTH)),
+    );
+
+    for (rowidx, row) in game.visible_grid().iter().enumerate() {
+        for (colidx, block) in row.iter().enumerate() {
+            if block.value != 0 {
+                canvas.block(rowidx + 1, colidx * 2 + 1, block.color.unwrap());
+            }
+        }
+    }
+    if game.entry_delay == 0 && !game.is_over() {
+        for Coord { y, x } in game.piece().cells() {
+            if y >= 0 {
+                canvas.block(y as usize + 1, x as usize * 2 + 1, game.piece().color);
+            }
+        }
+    }
+
+    if game.has_next() {
+        let next = &game.next_tetromino;
+        canvas.text(1, BOARD_WIDTH + 7, "NEXT");
+        let tetrovec = next.shape.to_4x4(next.current_rotation);
+        for (rowidx, row) in tetrovec.into_iter().enumerate() {
+            for (colidx, column) in row.into_iter().enumerate() {
+                if column != 0 {
+                    canvas.block(rowidx + 3, colidx * 2 + BOARD_WIDTH + 3, next.color);
+                }
+            }
+        }
+    }
+    if let Some(shape) = game.hold() {
+        canvas.text(8, BOARD_WIDTH + 7, "HOLD");
+        let tetrovec = shape.to_4x4(shape.get_possible_rotations()[0]);
+        for (rowidx, row) in tetrovec.into_iter().enumerate() {
+            for (colidx, column) in row.into_iter().enumerate() {
+                if column != 0 {
+                    canvas.block(rowidx + 10, colidx * 2 + BOARD_WIDTH + 3, shape.get_color());
+                }
+            }
+        }
+    }
+    if let Some(grade) = game.settings.mode.grade(game.score()) {
+        canvas.text(15, BOARD_WIDTH + 5, &format!("GRADE: {}", grade));
+        canvas.text(17, BOARD_WIDTH + 5, &format!("LEVEL: {}", game.level));
+    }
+
+    canvas.text(HEIGHT - 1, 0, &format!("SCORE: {}", game.score()));
+    if game.is_over() {
+        let outcome = game.outcome();
+        canvas.text(HEIGHT - 1, BOARD_WIDTH + 2 - outcome.len(), outcome);
+    }
+
+    canvas.to_ansi()
+}
+
+struct Canvas {
+    cells: Vec<Vec<(char, Option<Color>)>>,
+}
+
+impl Canvas {
+    fn new() -> Canvas {
+        Canvas {
+            cells: vec![vec![(' ', None); WIDTH]; HEIGHT],
+        }
+    }
+
+    fn text(&mut self, y: usize, x: usize, text: &str) {
+        for (idx, c) in text.chars().enumerate() {
+            if let Some(cell) = self.cells[y].get_mut(x + idx) {
+                *cell = (c, None);
+            }
+        }
+    }
+
+    fn block(&mut self, y: usize, x: usize, color: Color) {
+        for cell in self.cells[y].iter_mut().skip(x).take(2) {
+            *cell = ('█', Some(color));
+        }
+    }
+
+    fn to_ansi(&self) -> String {
+        let mut lines = vec![];
+        for row in &self.cells {
+            let mut line = String::new();
+            let mut current = None;
+            for (c, color) in row {
+                if *color != current {
+                    match color {
+                        Some(color) => write!(line, "\x1b[{}m", ansi(*color)).unwrap(),
+                        None => line.push_str("\x1b[0m"),
+                    }
+                    current = *color;
+                }
+                line.push(*c);
+            }
+            if current.is_some() {
+                line.push_str("\x1b[0m");
+            }
+            lines.push(line);
+        }
+        lines.join("\r\n")
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The ANSI foreground code for a color, bright black standing in for gray.
+fn ansi(color: Color) -> i32 {
+    match color {
+        Color::Gray => 90,
+        color => 30 + color as i32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Action;
+    use crate::mode::Settings;
+
+    fn replay() -> Replay {
+        let mut game = Game::with_seed(Settings::default(), 5);
+        for action in [Action::MoveLeft, Action::HardDrop].iter() {
+            game.apply(*action).unwrap();
+        }
+        for _ in 0..100 {
+            game.tick();
+        }
+        Replay::record(&game)
+    }
+
+    #[test]
+    fn export_header_and_events() {
+        let cast = export(&replay());
+        let mut lines = cast.lines();
+        assert_eq!(
+            lines.next(),
+            Some("{\"version\": 2, \"width\": 40, \"height\": 19}")
+        );
+
+        let mut previous = -1.0;
+        let mut events = 0;
+        for line in lines {
+            assert!(line.starts_with('[') && line.ends_with("\"]"), "{}", line);
+            assert!(line.contains(", \"o\", \""));
+            assert!(!line[1..].contains('\n'));
+            let time = line[1..line.find(',').unwrap()].parse::<f64>().unwrap();
+            assert!(time > previous);
+            previous = time;
+            events += 1;
+        }
+        assert!(events > 1);
+        assert!(previous <= 100.0 / FRAMES_PER_SECOND as f64);
+    }
+
+    #[test]
+    fn export_is_deterministic() {
+        let replay = replay();
+        assert_eq!(export(&replay), export(&replay));
+    }
+
+    #[test]
+    fn render_contents() {
+        let mut game = Game::with_seed(Settings::default(), 5);
+        game.score = 120;
+        let screen = render(&game);
+        let lines = screen.split("\r\n").collect::<Vec<&str>>();
+        assert_eq!(lines.len(), HEIGHT);
+        assert!(lines[0].starts_with("┌────"));
+        assert!(lines[1].contains("NEXT"));
+        assert_eq!(lines[HEIGHT - 1].trim_end(), "SCORE: 120");
+        assert_eq!(screen.matches('█').count(), 16);
+        assert!(screen.contains(&format!("\x1b[{}m", 30 + game.piece().color as i32)));
+        assert!(!screen.contains("\x1b[90m"));
+        game.add_garbage(1, 0);
+        assert!(render(&game).contains("\x1b[90m"));
+
+        game.over = true;
+        assert!(render(&game).contains("GAME OVER"));
+    }
+
+    #[test]
+    fn escape_json() {
+        assert_eq!(escape("a\"b\\c"), "a\\\"b\\\\c");
+        assert_eq!(escape("\x1b[H\r\n"), "\\u001b[H\\r\\n");
+        assert_eq!(escape("██"), "██");
+    }
+}
@@ -0,0 +1,436 @@
+use std::str::FromStr;
+use std::time::Duration;
+use tetris_rs::mode::{Mode, Settings};
+use tetris_rs::opener::Opener;
+use tetris_rs::puzzle;
+use tetris_rs::shape::Shape;
+use tetris_rs::{ai, bench, pc};
+
+pub const USAGE: &str =
+    "usage: tetris-rs [--mode classic|master|puzzle] [--challenge none|invisible|fading] [--big]
+                 [--resume] [--puzzle <file>] [--record <file>] [--replay <file> [--cast <file>]]
+                 [--review | --report] [--finesse | --drill] [--opener tki|dt-cannon|pco|mko]
+                 [--distribution] [--versus [--attack <file>] [--ai [--depth <n>] [--pps <n>]]]
+                 [--seed <n>] [--ai [--depth <n>] [--pps <n>] | --tbp <command>]
+                 [--headless [--pieces <n>]]
+       tetris-rs bench [--games <n>] [--seed <n>] [--bot heuristic|random] [--depth <n>]
+                 [--mode classic|master] [--big] [--pieces <n>] [--threads <n>] [--json]
+       tetris-rs tbp [--depth <n>]
+       tetris-rs pc [--puzzle <file>] [--queue <pieces>] [--hold <piece>] [--depth <n>]
+                 [--timeout <seconds>]";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    pub settings: Settings,
+    pub resume: bool,
+    pub puzzle: Option<String>,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    pub cast: Option<String>,
+    /// Steps through the replayed game next to the AI's placements.
+    pub review: bool,
+    /// Prints how the replayed game's placements compare to the AI's.
+    pub report: bool,
+    /// Counts the key presses wasted on each placement.
+    pub finesse: bool,
+    /// Turns down key presses that waste any, counting them as well.
+    pub drill: bool,
+    /// Shows the opener's template to build the stack on.
+    pub opener: Option<Opener>,
+    /// Shows how many of each shape have spawned.
+    pub distribution: bool,
+    /// Two players side by side on one keyboard, sending each other garbage.
+    pub versus: bool,
+    /// The attack table versus games read how much garbage clears send from.
+    pub attack: Option<String>,
+    pub seed: Option<u64>,
+    /// Lets the bot play instead of the keyboard.
+    pub ai: bool,
+    pub depth: Option<usize>,
+    pub pps: Option<f64>,
+    /// Lets an external Tetris Bot Protocol bot, started with this command
+    /// line, play instead of the keyboard.
+    pub tbp: Option<String>,
+    /// Plays without the terminal and prints the result.
+    pub headless: bool,
+    pub pieces: Option<u32>,
+}
+
+pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--mode" => options.settings.mode = parse_value(&arg, args.next())?,
+            "--challenge" => options.settings.challenge = parse_value(&arg, args.next())?,
+            "--big" => options.settings.big = true,
+            "--resume" => options.resume = true,
+            "--puzzle" => {
+                options.puzzle = Some(parse_value(&arg, args.next())?);
+                options.settings.mode = Mode::Puzzle;
+            }
+            "--record" => options.record = Some(parse_value(&arg, args.next())?),
+            "--replay" => options.replay = Some(parse_value(&arg, args.next())?),
+            "--cast" => options.cast = Some(parse_value(&arg, args.next())?),
+            "--review" => options.review = true,
+            "--report" => options.report = true,
+            "--finesse" => options.finesse = true,
+            "--drill" => options.drill = true,
+            "--distribution" => options.distribution = true,
+            "--versus" => options.versus = true,
+            "--attack" => options.attack = Some(parse_value(&arg, args.next())?),
+            "--opener" => {
+                options.opener = Some(Opener::named(&parse_value::<String>(&arg, args.next())?)?)
+            }
+            "--seed" => options.seed = Some(parse_value(&arg, args.next())?),
+            "--ai" => options.ai = true,
+            "--depth" => options.depth = Some(parse_value(&arg, args.next())?),
+            "--pps" => options.pps = Some(parse_value(&arg, args.next())?),
+            "--tbp" => options.tbp = Some(parse_value(&arg, args.next())?),
+            "--headless" => options.headless = true,
+            "--pieces" => options.pieces = Some(parse_value(&arg, args.next())?),
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+    if options.cast.is_some() && options.replay.is_none() {
+        return Err("--cast needs --replay".to_string());
+    }
+    if (options.review || options.report) && options.replay.is_none() {
+        return Err("--review and --report need --replay".to_string());
+    }
+    if (options.finesse || options.drill) && (options.ai || options.tbp.is_some()) {
+        return Err("--finesse and --drill are for playing yourself".to_string());
+    }
+    if options.opener.is_some() && (options.settings.big || options.puzzle.is_some()) {
+        return Err("--opener does not work with --big or --puzzle".to_string());
+    }
+    if options.attack.is_some() && !options.versus {
+        return Err("--attack needs --versus".to_string());
+    }
+    // The options a versus game takes; the rest are for playing alone.
+    let versus = Options {
+        settings: Settings {
+            mode: options.settings.mode,
+            challenge: options.settings.challenge,
+            ..Settings::default()
+        },
+        seed: options.seed,
+        ai: options.ai,
+        depth: options.depth,
+        pps: options.pps,
+        versus: true,
+        attack: options.attack.clone(),
+        ..Options::default()
+    };
+    if options.versus && (options != versus || options.settings.mode == Mode::Puzzle) {
+        return Err(
+            "--versus only goes with --mode classic|master, --challenge, --seed, --attack and --ai"
+                .to_string(),
+        );
+    }
+    if (options.depth.is_some() || options.pps.is_some()) && !options.ai {
+        return Err("--depth and --pps need --ai".to_string());
+    }
+    if options.ai && options.tbp.is_some() {
+        return Err("--ai and --tbp cannot be combined".to_string());
+    }
+    if options.headless && !options.ai && options.tbp.is_none() {
+        return Err("--headless needs --ai or --tbp".to_string());
+    }
+    if options.pieces.is_some() && !options.headless {
+        return Err("--pieces needs --headless".to_string());
+    }
+    if options.depth == Some(0) || options.pps.is_some_and(|pps| pps <= 0.0) {
+        return Err("--depth and --pps must be positive".to_string());
+    }
+    Ok(options)
+}
+
+/// Options for the `bench` subcommand.
+#[derive(Debug, Default, PartialEq)]
+pub struct BenchOptions {
+    pub config: bench::Config,
+    pub json: bool,
+}
+
+pub fn parse_bench<I: Iterator<Item = String>>(mut args: I) -> Result<BenchOptions, String> {
+    let mut options = BenchOptions::default();
+    let config = &mut options.config;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => config.games = parse_value(&arg, args.next())?,
+            "--seed" => config.seed = parse_value(&arg, args.next())?,
+            "--bot" => config.bot = parse_value(&arg, args.next())?,
+            "--depth" => config.depth = parse_value(&arg, args.next())?,
+            "--mode" => config.settings.mode = parse_value(&arg, args.next())?,
+            "--big" => config.settings.big = true,
+            "--pieces" => config.pieces = parse_value(&arg, args.next())?,
+            "--threads" => config.threads = parse_value(&arg, args.next())?,
+            "--json" => options.json = true,
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+    if config.settings.mode == Mode::Puzzle {
+        return Err("bench does not play puzzles".to_string());
+    }
+    if config.depth == 0 || config.threads == 0 {
+        return Err("--depth and --threads must be positive".to_string());
+    }
+    Ok(options)
+}
+
+/// Options for the `tbp` subcommand.
+#[derive(Debug, PartialEq)]
+pub struct TbpOptions {
+    pub depth: usize,
+}
+
+pub fn parse_tbp<I: Iterator<Item = String>>(mut args: I) -> Result<TbpOptions, String> {
+    let mut options = TbpOptions {
+        depth: ai::DEFAULT_DEPTH,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--depth" => options.depth = parse_value(&arg, args.next())?,
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+    if options.depth == 0 {
+        return Err("--depth must be positive".to_string());
+    }
+    Ok(options)
+}
+
+/// Options for the `pc` subcommand.
+#[derive(Debug, Default, PartialEq)]
+pub struct PcOptions {
+    /// A puzzle file to take the board, and the queue unless given, from.
+    pub puzzle: Option<String>,
+    /// The active piece followed by the queue.
+    pub queue: Option<Vec<Shape>>,
+    pub hold: Option<Shape>,
+    pub limits: pc::Limits,
+}
+
+pub fn parse_pc<I: Iterator<Item = String>>(mut args: I) -> Result<PcOptions, String> {
+    let mut options = PcOptions::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--puzzle" => options.puzzle = Some(parse_value(&arg, args.next())?),
+            "--queue" => {
+                let value: String = parse_value(&arg, args.next())?;
+                options.queue = Some(puzzle::parse_queue(&value)?);
+            }
+            "--hold" => options.hold = Some(parse_value(&arg, args.next())?),
+            "--depth" => options.limits.depth = parse_value(&arg, args.next())?,
+            "--timeout" => {
+                let seconds: f64 = parse_value(&arg, args.next())?;
+                if !(seconds > 0.0 && seconds.is_finite()) {
+                    return Err("--timeout must be positive".to_string());
+                }
+                options.limits.timeout = Duration::from_secs_f64(seconds);
+            }
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+    if options.puzzle.is_none() && options.queue.is_none() {
+        return Err("pc needs --queue or --puzzle".to_string());
+    }
+    if options.limits.depth == 0 {
+        return Err("--depth must be positive".to_string());
+    }
+    Ok(options)
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or(format!("{} needs a value", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("unknown value for {}: {}", flag, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tetris_rs::mode::Challenge;
+
+    fn args(line: &str) -> impl Iterator<Item = String> + '_ {
+        line.split_whitespace().map(String::from)
+    }
+
+    #[test]
+    fn parse_defaults() {
+        assert_eq!(parse(args("")), Ok(Options::default()));
+    }
+
+    #[test]
+    fn parse_mode() {
+        assert_eq!(
+            parse(args("--mode master")).unwrap().settings.mode,
+            Mode::Master
+        );
+    }
+
+    #[test]
+    fn parse_challenge() {
+        let options = parse(args("--challenge invisible --mode master --big")).unwrap();
+        assert_eq!(options.settings.challenge, Challenge::Invisible);
+        assert_eq!(options.settings.mode, Mode::Master);
+        assert!(options.settings.big);
+    }
+
+    #[test]
+    fn parse_resume() {
+        assert!(parse(args("--resume")).unwrap().resume);
+    }
+
+    #[test]
+    fn parse_puzzle() {
+        let options = parse(args("--puzzle puzzles/tetris.txt")).unwrap();
+        assert_eq!(options.puzzle, Some("puzzles/tetris.txt".to_string()));
+        assert_eq!(options.settings.mode, Mode::Puzzle);
+    }
+
+    #[test]
+    fn parse_record() {
+        let options = parse(args("--record game.replay")).unwrap();
+        assert_eq!(options.record, Some("game.replay".to_string()));
+        let options = parse(args("--replay game.replay")).unwrap();
+        assert_eq!(options.replay, Some("game.replay".to_string()));
+    }
+
+    #[test]
+    fn parse_cast() {
+        let options = parse(args("--replay game.replay --cast game.cast")).unwrap();
+        assert_eq!(options.replay, Some("game.replay".to_string()));
+        assert_eq!(options.cast, Some("game.cast".to_string()));
+        assert!(parse(args("--cast game.cast")).is_err());
+    }
+
+    #[test]
+    fn parse_review() {
+        let options = parse(args("--replay game.replay --review")).unwrap();
+        assert!(options.review);
+        let options = parse(args("--report --replay game.replay")).unwrap();
+        assert!(options.report);
+        assert!(parse(args("--review")).is_err());
+        assert!(parse(args("--report")).is_err());
+    }
+
+    #[test]
+    fn parse_finesse() {
+        assert!(parse(args("--finesse")).unwrap().finesse);
+        assert!(parse(args("--drill --mode master")).unwrap().drill);
+        assert!(parse(args("--drill --ai")).is_err());
+        assert!(parse(args("--finesse --tbp ./bot")).is_err());
+    }
+
+    #[test]
+    fn parse_opener() {
+        let options = parse(args("--opener dt-cannon")).unwrap();
+        assert_eq!(options.opener.unwrap().name, "DT cannon");
+        assert!(parse(args("--opener stsd")).is_err());
+        assert!(parse(args("--opener tki --big")).is_err());
+        assert!(parse(args("--distribution")).unwrap().distribution);
+    }
+
+    #[test]
+    fn parse_versus() {
+        let options = parse(args("--versus --mode master --seed 3 --attack attack.txt")).unwrap();
+        assert!(options.versus);
+        assert_eq!(options.attack, Some("attack.txt".to_string()));
+        assert!(parse(args("--attack attack.txt")).is_err());
+        assert!(parse(args("--versus --big")).is_err());
+        assert!(parse(args("--versus --mode puzzle")).is_err());
+        assert!(parse(args("--versus --record game.replay")).is_err());
+        let options = parse(args("--versus --ai --depth 1 --pps 2")).unwrap();
+        assert!(options.versus && options.ai);
+        assert_eq!((options.depth, options.pps), (Some(1), Some(2.0)));
+        assert!(parse(args("--versus --tbp ./bot")).is_err());
+    }
+
+    #[test]
+    fn parse_ai() {
+        let options = parse(args("--ai --depth 3 --pps 1.5 --seed 7")).unwrap();
+        assert!(options.ai);
+        assert_eq!(options.depth, Some(3));
+        assert_eq!(options.pps, Some(1.5));
+        assert_eq!(options.seed, Some(7));
+        let options = parse(args("--ai --headless --pieces 50")).unwrap();
+        assert!(options.headless);
+        assert_eq!(options.pieces, Some(50));
+
+        assert!(parse(args("--depth 3")).is_err());
+        assert!(parse(args("--headless")).is_err());
+        assert!(parse(args("--ai --pieces 50")).is_err());
+        assert!(parse(args("--ai --depth 0")).is_err());
+        assert!(parse(args("--ai --pps -1")).is_err());
+    }
+
+    #[test]
+    fn parse_tbp_options() {
+        let options = parse(args("--tbp ./bot --headless")).unwrap();
+        assert_eq!(options.tbp, Some("./bot".to_string()));
+        assert!(options.headless);
+        assert!(parse(args("--tbp ./bot --ai")).is_err());
+        assert!(parse(args("--tbp ./bot --depth 2")).is_err());
+
+        assert_eq!(parse_tbp(args("--depth 3")), Ok(TbpOptions { depth: 3 }));
+        assert_eq!(parse_tbp(args("")).unwrap().depth, ai::DEFAULT_DEPTH);
+        assert!(parse_tbp(args("--depth 0")).is_err());
+        assert!(parse_tbp(args("--ai")).is_err());
+    }
+
+    #[test]
+    fn parse_bench_options() {
+        assert_eq!(parse_bench(args("")), Ok(BenchOptions::default()));
+        let options = parse_bench(args(
+            "--games 50 --seed 9 --bot random --mode master --threads 2 --json",
+        ))
+        .unwrap();
+        assert_eq!(options.config.games, 50);
+        assert_eq!(options.config.seed, 9);
+        assert_eq!(options.config.bot, bench::BotKind::Random);
+        assert_eq!(options.config.settings.mode, Mode::Master);
+        assert_eq!(options.config.threads, 2);
+        assert!(options.json);
+
+        assert!(parse_bench(args("--mode puzzle")).is_err());
+        assert!(parse_bench(args("--threads 0")).is_err());
+        assert!(parse_bench(args("--bot human")).is_err());
+        assert!(parse_bench(args("--resume")).is_err());
+    }
+
+    #[test]
+    fn parse_pc_options() {
+        let options = parse_pc(args("--queue IOLJ --hold T --depth 4 --timeout 0.5")).unwrap();
+        assert_eq!(
+            options.queue,
+            Some(vec![Shape::I, Shape::O, Shape::L, Shape::J])
+        );
+        assert_eq!(options.hold, Some(Shape::T));
+        assert_eq!(options.limits.depth, 4);
+        assert_eq!(options.limits.timeout, Duration::from_millis(500));
+        let options = parse_pc(args("--puzzle puzzles/perfect-clear.txt")).unwrap();
+        assert_eq!(options.queue, None);
+        assert_eq!(options.limits, pc::Limits::default());
+
+        assert!(parse_pc(args("")).is_err());
+        assert!(parse_pc(args("--queue IOX")).is_err());
+        assert!(parse_pc(args("--queue IO --depth 0")).is_err());
+        assert!(parse_pc(args("--queue IO --timeout 0")).is_err());
+        assert!(parse_pc(args("--queue IO --hold")).is_err());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse(args("--mode")).is_err());
+        assert!(parse(args("--mode zen")).is_err());
+        assert!(parse(args("--challenge")).is_err());
+        assert!(parse(args("--puzzle")).is_err());
+        assert!(parse(args("--record")).is_err());
+        assert!(parse(args("--replay")).is_err());
+        assert!(parse(args("--replay game.replay --cast")).is_err());
+        assert!(parse(args("--seed x")).is_err());
+        assert!(parse(args("--speed 3")).is_err());
+    }
+}
@@ -1,28 +1,229 @@
+use crate::event::{ClearType, Event, Subscriber, EVENT_QUEUE_LENGTH};
+use crate::mode::{Settings, G, MASTER_MAX_LEVEL, REVEAL_FRAMES, SECTION_LENGTH};
+use crate::puzzle::Puzzle;
+use crate::random::Random;
+use crate::shape::Shape;
 use crate::tetromino::Tetromino;
-use crate::ui::{curses_teardown, Color};
+use crate::ui::Color;
+use std::collections::VecDeque;
+use strum_macros::{Display, EnumIter, EnumString};
 
 pub const PLAYGROUND_WIDTH: i32 = 10;
 pub const PLAYGROUND_HEIGHT: i32 = 16;
+pub const FRAMES_PER_SECOND: u32 = 60;
+pub const HISTORY_LENGTH: usize = 100;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Game {
-    pub grid: Grid,
-    pub tetromino: Tetromino,
-    pub next_tetromino: Tetromino,
-    pub paused: bool,
-    pub score: u64,
-    counter: u8,
+    grid: Grid,
+    tetromino: Tetromino,
+    pub(crate) next_tetromino: Tetromino,
+    pub settings: Settings,
+    pub(crate) paused: bool,
+    pub(crate) over: bool,
+    pub(crate) score: u64,
+    pub lines: u32,
+    pub pieces: u32,
+    pub level: u32,
+    pub frame: u64,
+    pub entry_delay: u32,
+    pub reveal_until: u64,
+    pub section_times: Vec<u64>,
+    pub puzzle: Option<Puzzle>,
+    pub solved: bool,
+    pub seed: u64,
+    pub used_undo: bool,
+    /// Whether the placement hint was shown at some point.
+    pub used_hint: bool,
+    /// The piece put aside with `Action::Hold`.
+    held: Option<Shape>,
+    /// Whether hold was used since the last piece locked.
+    pub hold_used: bool,
+    /// Every action applied so far, for replays.
+    pub inputs: Vec<Input>,
+    /// Pieces lined up after the next one, such as the rest of a puzzle's,
+    /// before the randomizer deals more.
+    queue: VecDeque<Shape>,
+    pub(crate) rng: Random,
+    pub(crate) section_start: u64,
+    pub(crate) gravity_progress: u32,
+    pub(crate) lock_progress: u32,
+    pub(crate) combo: u32,
+    /// Moves and rotations made with the active piece, for finesse.
+    presses: u32,
+    history: VecDeque<Snapshot>,
+    future: Vec<Snapshot>,
+    /// Whether the active piece's last successful move was a rotation, for T-spins.
+    last_rotated: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    events: VecDeque<Event>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    subscribers: Vec<Subscriber>,
 }
 
 impl Game {
-    pub fn new() -> Game {
+    pub fn new(settings: Settings) -> Game {
+        Game::with_seed(settings, rand::random())
+    }
+
+    pub fn with_seed(settings: Settings, seed: u64) -> Game {
         let grid = Game::create_grid();
+        let mut rng = Random::new(seed);
         Game {
-            tetromino: Tetromino::new(grid),
-            next_tetromino: Tetromino::new(grid),
+            tetromino: Tetromino::new(grid, settings.scale(), &mut rng),
+            next_tetromino: Tetromino::new(grid, settings.scale(), &mut rng),
             grid,
+            settings,
             score: 0,
-            counter: 0,
+            lines: 0,
+            pieces: 0,
+            level: 0,
+            frame: 0,
+            entry_delay: 0,
+            reveal_until: 0,
+            section_times: vec![],
+            puzzle: None,
+            solved: false,
+            seed,
+            used_undo: false,
+            used_hint: false,
+            held: None,
+            hold_used: false,
+            inputs: vec![],
+            queue: VecDeque::new(),
+            rng,
+            history: VecDeque::new(),
+            future: vec![],
+            last_rotated: false,
+            events: VecDeque::new(),
+            subscribers: vec![],
+            section_start: 0,
+            gravity_progress: 0,
+            lock_progress: 0,
+            combo: 1,
+            presses: 0,
             paused: false,
+            over: false,
+        }
+    }
+
+    /// Replaces the board and upcoming pieces with the ones from the puzzle.
+    pub fn load_puzzle(&mut self, puzzle: Puzzle) {
+        self.grid = puzzle.board;
+        self.queue = puzzle.queue.iter().copied().collect();
+        self.tetromino = self.create_tetromino();
+        self.next_tetromino = self.create_tetromino();
+        self.puzzle = Some(puzzle);
+    }
+
+    /// Sets up a position to play from: the locked blocks, the active piece,
+    /// the pieces lined up after the next one and the held piece.
+    pub fn set_position(
+        &mut self,
+        board: Grid,
+        piece: Tetromino,
+        queue: Vec<Shape>,
+        hold: Option<Shape>,
+    ) {
+        self.grid = board;
+        self.tetromino = Tetromino {
+            grid: board,
+            ..piece
+        };
+        self.next_tetromino.grid = board;
+        self.queue = queue.into();
+        self.held = hold;
+    }
+
+    /// Whether there is a piece after the current one.
+    pub fn has_next(&self) -> bool {
+        match &self.puzzle {
+            Some(puzzle) => (self.pieces as usize) + 1 < puzzle.queue.len(),
+            None => true,
+        }
+    }
+
+    /// The locked blocks, without the active piece.
+    pub fn board(&self) -> &Grid {
+        &self.grid
+    }
+
+    /// The piece being played.
+    pub fn piece(&self) -> &Tetromino {
+        &self.tetromino
+    }
+
+    /// The pieces coming up after the active one, in order.
+    pub fn queue(&self) -> Vec<Shape> {
+        if !self.has_next() {
+            return vec![];
+        }
+        let mut queue = vec![self.next_tetromino.shape];
+        queue.extend(&self.queue);
+        queue
+    }
+
+    pub fn hold(&self) -> Option<Shape> {
+        self.held
+    }
+
+    pub fn score(&self) -> u64 {
+        self.score
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.over
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn state(&self) -> State {
+        if self.over {
+            State::Over
+        } else if self.paused {
+            State::Paused
+        } else if self.entry_delay > 0 {
+            State::EntryDelay
+        } else {
+            State::Playing
+        }
+    }
+
+    /// Takes the events that happened since the last call, oldest first.
+    pub fn drain_events(&mut self) -> Vec<Event> {
+        self.events.drain(..).collect()
+    }
+
+    /// Calls `callback` with every event as it happens.
+    pub fn subscribe(&mut self, callback: impl FnMut(&Event) + 'static) {
+        self.subscribers.push(Box::new(callback));
+    }
+
+    fn emit(&mut self, event: Event) {
+        for subscriber in &mut self.subscribers {
+            subscriber(&event);
+        }
+        if self.events.len() == EVENT_QUEUE_LENGTH {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    fn end(&mut self) {
+        self.over = true;
+        self.emit(Event::GameOver);
+    }
+
+    fn create_tetromino(&mut self) -> Tetromino {
+        let scale = self.settings.scale();
+        match self.queue.pop_front() {
+            Some(shape) => {
+                let rotation = shape.get_possible_rotations()[0];
+                Tetromino::from_shape(self.grid, scale, shape, rotation)
+            }
+            None => Tetromino::new(self.grid, scale, &mut self.rng),
         }
     }
 
@@ -34,78 +235,466 @@ impl Game {
         [Block::new(0, None); PLAYGROUND_WIDTH as usize]
     }
 
-    pub fn clear_rows(&mut self) {
+    /// Clears the full rows and returns their indices.
+    pub fn clear_rows(&mut self) -> Vec<usize> {
+        let mut cleared = vec![];
         for i in 0..self.grid.len() {
             if self.grid[i].iter().fold(0, |acc, x| acc + x.value) as i32 == PLAYGROUND_WIDTH {
                 let row = Game::create_empty_row();
                 self.grid[i] = row;
                 self.grid[..i + 1].rotate_right(1);
                 self.tetromino.grid = self.grid;
-                self.score += PLAYGROUND_WIDTH as u64;
+                cleared.push(i);
+            }
+        }
+        cleared
+    }
+
+    /// Advances the game by one frame, applying gravity, lock delay and entry delay.
+    pub fn tick(&mut self) {
+        self.frame += 1;
+        if self.entry_delay > 0 {
+            self.entry_delay -= 1;
+            return;
+        }
+
+        let topleft = self.tetromino.topleft;
+        self.gravity_progress += self.settings.mode.gravity(self.level);
+        while self.gravity_progress >= G {
+            self.gravity_progress -= G;
+            if self.tetromino.move_down().is_ok() {
+                self.lock_progress = 0;
+                self.last_rotated = false;
+            } else {
+                self.gravity_progress = 0;
+            }
+        }
+        if self.tetromino.topleft != topleft {
+            self.emit(Event::Moved {
+                topleft: self.tetromino.topleft,
+            });
+        }
+
+        if self.tetromino.is_grounded() {
+            self.lock_progress += 1;
+            if self.lock_progress >= self.settings.mode.delays(self.level).lock {
+                self.lock_tetromino();
+            }
+        }
+    }
+
+    fn lock_tetromino(&mut self) {
+        self.history.push_back(self.snapshot());
+        if self.history.len() > HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.future.clear();
+
+        let t_spin = self.tetromino.shape == Shape::T
+            && self.last_rotated
+            && self.tetromino.filled_corners() >= 3;
+        if self.land_tetromino().is_err() {
+            self.end();
+            return;
+        }
+        self.emit(Event::Locked {
+            shape: self.tetromino.shape,
+            cells: self.tetromino.cells(),
+            presses: self.presses,
+        });
+
+        self.hold_used = false;
+        let rows = self.clear_rows();
+        let lines = rows.len() as u32 / self.settings.scale() as u32;
+        if let Some(clear) = ClearType::new(lines, t_spin) {
+            self.emit(Event::LinesCleared { rows, clear });
+        }
+        self.lines += lines;
+        self.pieces += 1;
+        if let Some(puzzle) = &self.puzzle {
+            if puzzle.goal.is_met(self.lines, &self.grid) {
+                self.solved = true;
+                self.end();
+            } else if self.pieces as usize >= puzzle.queue.len() {
+                self.end();
+            }
+        }
+
+        let delays = self.settings.mode.delays(self.level);
+        if lines > 0 {
+            self.combo += 2 * lines - 2;
+            self.score += self.settings.mode.score(self.level, lines, self.combo);
+            self.advance_level(lines);
+            self.reveal_until = self.frame + REVEAL_FRAMES;
+            self.entry_delay = delays.clear + delays.line_entry;
+        } else {
+            self.combo = 1;
+            self.entry_delay = delays.entry;
+        }
+
+        self.spawn_tetromino();
+    }
+
+    fn spawn_tetromino(&mut self) {
+        self.tetromino = self.next_tetromino.clone();
+        self.tetromino.grid = self.grid;
+        self.next_tetromino = self.create_tetromino();
+        self.gravity_progress = 0;
+        self.lock_progress = 0;
+        self.last_rotated = false;
+        self.presses = 0;
+        self.emit(Event::Spawned {
+            shape: self.tetromino.shape,
+            rotation: self.tetromino.current_rotation,
+        });
+
+        let level_stop =
+            self.level % SECTION_LENGTH == SECTION_LENGTH - 1 || self.level == MASTER_MAX_LEVEL - 1;
+        if !level_stop {
+            self.advance_level(1);
+        }
+    }
+
+    fn advance_level(&mut self, amount: u32) {
+        if !self.settings.mode.has_levels() || self.over {
+            return;
+        }
+
+        let section = self.level / SECTION_LENGTH;
+        self.level = (self.level + amount).min(MASTER_MAX_LEVEL);
+        self.emit(Event::LevelUp { level: self.level });
+        if self.level / SECTION_LENGTH > section || self.level == MASTER_MAX_LEVEL {
+            self.section_times.push(self.frame - self.section_start);
+            self.section_start = self.frame;
+        }
+        if self.level == MASTER_MAX_LEVEL {
+            self.end();
+        }
+    }
+
+    /// Applies a player action on the current frame and records it.
+    pub fn apply(&mut self, action: Action) -> Result<(), &'static str> {
+        self.inputs.push(Input {
+            frame: self.frame,
+            action,
+        });
+        match action {
+            Action::Undo => return self.undo(),
+            Action::Redo => return self.redo(),
+            _ => {}
+        }
+
+        if self.entry_delay > 0 {
+            return Err("Entry delay.");
+        }
+        if !matches!(action, Action::SoftDrop | Action::HardDrop | Action::Hold) {
+            self.presses += 1;
+        }
+        let topleft = self.tetromino.topleft;
+        match action {
+            Action::MoveLeft => self.tetromino.move_sideways(Direction::Left)?,
+            Action::MoveRight => self.tetromino.move_sideways(Direction::Right)?,
+            Action::SoftDrop => self.tetromino.move_down()?,
+            Action::HardDrop => self.tetromino.move_all_the_way_down(),
+            Action::RotateLeft => self.tetromino.rotate(Direction::Left)?,
+            Action::RotateRight => self.tetromino.rotate(Direction::Right)?,
+            Action::Rotate180 => self.tetromino.rotate_180()?,
+            Action::Hold => return self.swap_hold(),
+            Action::Undo | Action::Redo => unreachable!(),
+        }
+
+        if let Action::RotateLeft | Action::RotateRight | Action::Rotate180 = action {
+            self.last_rotated = true;
+            self.emit(Event::Rotated {
+                rotation: self.tetromino.current_rotation,
+                kick: 0,
+            });
+        } else if self.tetromino.topleft != topleft {
+            self.last_rotated = false;
+            self.emit(Event::Moved {
+                topleft: self.tetromino.topleft,
+            });
+        }
+        Ok(())
+    }
+
+    /// Puts the active piece aside and brings back the one held before, or the
+    /// next piece if nothing was held. Allowed once per piece.
+    fn swap_hold(&mut self) -> Result<(), &'static str> {
+        if self.puzzle.is_some() {
+            return Err("No hold in puzzles.");
+        }
+        if self.hold_used {
+            return Err("Hold already used.");
+        }
+        let shape = self.tetromino.shape;
+        match self.held.replace(shape) {
+            Some(held) => {
+                let rotation = held.get_possible_rotations()[0];
+                self.tetromino =
+                    Tetromino::from_shape(self.grid, self.settings.scale(), held, rotation);
+            }
+            None => {
+                self.tetromino = self.next_tetromino.clone();
+                self.tetromino.grid = self.grid;
+                self.next_tetromino = self.create_tetromino();
             }
         }
+        self.hold_used = true;
+        self.gravity_progress = 0;
+        self.lock_progress = 0;
+        self.last_rotated = false;
+        let presses = std::mem::take(&mut self.presses);
+        self.emit(Event::HoldUsed {
+            held: shape,
+            presses,
+        });
+        self.emit(Event::Spawned {
+            shape: self.tetromino.shape,
+            rotation: self.tetromino.current_rotation,
+        });
+        Ok(())
+    }
+
+    /// Takes back the last placement.
+    pub fn undo(&mut self) -> Result<(), &'static str> {
+        let snapshot = self.history.pop_back().ok_or("Nothing to undo.")?;
+        self.future.push(self.snapshot());
+        self.restore(snapshot);
+        self.used_undo = true;
+        Ok(())
+    }
+
+    /// Puts back a placement taken back with `undo`.
+    pub fn redo(&mut self) -> Result<(), &'static str> {
+        let snapshot = self.future.pop().ok_or("Nothing to redo.")?;
+        self.history.push_back(self.snapshot());
+        self.restore(snapshot);
+        Ok(())
+    }
+
+    /// Whether the game can go into the high scores, i.e. no assists were used.
+    pub fn is_high_score_eligible(&self) -> bool {
+        !self.used_undo && !self.used_hint
     }
 
-    pub fn handle_falling(&mut self) {
-        self.counter += 1;
-        if self.counter == 5 {
-            if self.tetromino.move_down().is_err() {
-                if self.land_tetromino().is_err() {
-                    curses_teardown();
-                    std::process::exit(0);
+    /// Pushes the stack up with rows of garbage, each with a hole in the
+    /// given column. The game is over if the stack is pushed out of the top or
+    /// into the active piece.
+    pub fn add_garbage(&mut self, lines: u32, hole: usize) {
+        let lines = (lines as usize).min(self.grid.len());
+        if lines == 0 {
+            return;
+        }
+        let overflow = self.grid[..lines]
+            .iter()
+            .flatten()
+            .any(|block| block.value != 0);
+        self.grid.rotate_left(lines);
+        let bottom = self.grid.len() - lines;
+        for row in self.grid[bottom..].iter_mut() {
+            for (x, block) in row.iter_mut().enumerate() {
+                *block = if x == hole {
+                    Block::new(0, None)
                 } else {
-                    self.tetromino = self.next_tetromino.clone();
-                    self.tetromino.grid = self.grid;
-                    self.next_tetromino = Tetromino::new(self.grid);
-                }
+                    Block {
+                        value: 1,
+                        color: Some(Color::Gray),
+                        locked_at: self.frame,
+                    }
+                };
             }
-            self.counter = 0;
+        }
+        self.tetromino.grid = self.grid;
+        self.emit(Event::GarbageReceived {
+            lines: lines as u32,
+        });
+        let blocked = self
+            .tetromino
+            .cells()
+            .iter()
+            .any(|cell| self.grid[cell.y as usize][cell.x as usize].value != 0);
+        if overflow || blocked {
+            self.end();
         }
     }
 
+    /// What to show once the game is over.
+    pub fn outcome(&self) -> &'static str {
+        match (&self.puzzle, self.solved) {
+            (Some(_), true) => "SOLVED",
+            (Some(_), false) => "FAILED",
+            (None, _) => "GAME OVER",
+        }
+    }
+
+    /// Takes over the undo and redo history of another game, e.g. one played
+    /// back to the same point from a replay.
+    pub fn take_history(&mut self, other: Game) {
+        self.history = other.history;
+        self.future = other.future;
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let mut tetromino = self.tetromino.clone();
+        tetromino.topleft = Tetromino::spawn_position(tetromino.scale);
+        Snapshot {
+            grid: self.grid,
+            tetromino,
+            next_tetromino: self.next_tetromino.clone(),
+            queue: self.queue.clone(),
+            score: self.score,
+            lines: self.lines,
+            pieces: self.pieces,
+            level: self.level,
+            combo: self.combo,
+            rng: self.rng,
+            held: self.held,
+            hold_used: self.hold_used,
+            reveal_until: self.reveal_until,
+            section_times: self.section_times.clone(),
+            section_start: self.section_start,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.grid = snapshot.grid;
+        self.tetromino = snapshot.tetromino;
+        self.next_tetromino = snapshot.next_tetromino;
+        self.queue = snapshot.queue;
+        self.score = snapshot.score;
+        self.lines = snapshot.lines;
+        self.pieces = snapshot.pieces;
+        self.level = snapshot.level;
+        self.combo = snapshot.combo;
+        self.rng = snapshot.rng;
+        self.held = snapshot.held;
+        self.hold_used = snapshot.hold_used;
+        self.reveal_until = snapshot.reveal_until;
+        self.section_times = snapshot.section_times;
+        self.section_start = snapshot.section_start;
+        self.entry_delay = 0;
+        self.gravity_progress = 0;
+        self.lock_progress = 0;
+        self.presses = 0;
+    }
+
+    /// The grid with the blocks hidden by the current challenge left out.
+    pub fn visible_grid(&self) -> Grid {
+        let mut grid = self.grid;
+        if self.over || self.frame < self.reveal_until {
+            return grid;
+        }
+        for block in grid.iter_mut().flatten() {
+            if !self.settings.challenge.shows(self.frame - block.locked_at) {
+                *block = Block::new(0, None);
+            }
+        }
+        grid
+    }
+
+    pub fn section_time(&self) -> u64 {
+        self.frame - self.section_start
+    }
+
     fn land_tetromino(&mut self) -> Result<(), &'static str> {
         if self.tetromino.topleft.y <= 0 {
             return Err("Game over.");
         }
 
-        let current_rotation = self.tetromino.current_rotation;
-        let tetrovec = self.tetromino.shape.to_4x4(current_rotation);
-
-        for (rowidx, row) in tetrovec.into_iter().enumerate() {
-            for (colidx, column) in row.into_iter().enumerate() {
-                if column != 0 {
-                    let Coord { y, x } = self.tetromino.topleft;
-                    self.grid[rowidx + y as usize][(colidx as i32 + x as i32) as usize] = Block {
-                        value: column as u8,
-                        color: Some(self.tetromino.color),
-                    }
-                }
+        for Coord { y, x } in self.tetromino.cells() {
+            self.grid[y as usize][x as usize] = Block {
+                value: 1,
+                color: Some(self.tetromino.color),
+                locked_at: self.frame,
             }
         }
         Ok(())
     }
 }
 
+/// The state needed to take a placement back, saved every time a piece locks.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Snapshot {
+    grid: Grid,
+    tetromino: Tetromino,
+    next_tetromino: Tetromino,
+    queue: VecDeque<Shape>,
+    score: u64,
+    lines: u32,
+    pieces: u32,
+    level: u32,
+    combo: u32,
+    rng: Random,
+    held: Option<Shape>,
+    hold_used: bool,
+    reveal_until: u64,
+    section_times: Vec<u64>,
+    section_start: u64,
+}
+
 pub type Grid = [[Block; PLAYGROUND_WIDTH as usize]; PLAYGROUND_HEIGHT as usize];
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Block {
     pub value: u8,
     pub color: Option<Color>,
+    /// The frame the block landed on.
+    pub locked_at: u64,
 }
 
 impl Block {
     pub fn new(value: u8, color: Option<Color>) -> Block {
-        Block { value, color }
+        Block {
+            value,
+            color,
+            locked_at: 0,
+        }
     }
 }
 
+#[derive(Clone, Copy, Debug, Display, EnumIter, EnumString, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[strum(serialize_all = "kebab-case")]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateLeft,
+    RotateRight,
+    Rotate180,
+    Hold,
+    Undo,
+    Redo,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum State {
+    Playing,
+    /// Waiting for the next piece to appear.
+    EntryDelay,
+    Paused,
+    Over,
+}
+
+/// An action together with the frame it was applied on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Input {
+    pub frame: u64,
+    pub action: Action,
+}
+
 #[derive(Clone, Copy)]
 pub enum Direction {
     Left = -1,
     Right = 1,
 }
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Coord {
     pub y: i32,
     pub x: i32,
@@ -114,6 +703,15 @@ pub struct Coord {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mode::{Challenge, Mode, FADE_FRAMES};
+
+    fn game(mode: Mode, challenge: Challenge) -> Game {
+        Game::new(Settings {
+            mode,
+            challenge,
+            ..Settings::default()
+        })
+    }
 
     #[test]
     fn create_grid() {
@@ -133,7 +731,8 @@ mod tests {
                 row[i as usize],
                 Block {
                     value: 0,
-                    color: None
+                    color: None,
+                    locked_at: 0
                 }
             );
         }
@@ -141,7 +740,7 @@ mod tests {
 
     #[test]
     fn clear_rows() {
-        let mut game = Game::new();
+        let mut game = game(Mode::Classic, Challenge::None);
         for row in 0..PLAYGROUND_HEIGHT {
             for column in 0..PLAYGROUND_WIDTH {
                 if row > ((PLAYGROUND_HEIGHT / 4) * 3) - 1 {
@@ -155,7 +754,8 @@ mod tests {
             game.grid[row_above_last_quarter][column] = Block::new(1, None);
         }
 
-        game.clear_rows();
+        let last_quarter = (PLAYGROUND_HEIGHT / 4 * 3) as usize..PLAYGROUND_HEIGHT as usize;
+        assert_eq!(game.clear_rows(), last_quarter.collect::<Vec<usize>>());
 
         for column in 4..7 {
             assert_eq!(
@@ -164,4 +764,614 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn classic_lock_delay() {
+        let mut game = game(Mode::Classic, Challenge::None);
+        game.tetromino.move_all_the_way_down();
+        let lock_delay = Mode::Classic.delays(0).lock;
+        for _ in 1..lock_delay {
+            game.tick();
+        }
+        assert_eq!(game.grid, Game::create_grid());
+        game.tick();
+        assert_ne!(game.grid, Game::create_grid());
+        assert_eq!(game.entry_delay, 0);
+    }
+
+    #[test]
+    fn master_entry_delay() {
+        let mut game = game(Mode::Master, Challenge::None);
+        game.tetromino.move_all_the_way_down();
+        for _ in 0..Mode::Master.delays(0).lock {
+            game.tick();
+        }
+        assert_eq!(game.entry_delay, Mode::Master.delays(0).entry);
+        assert_eq!(game.level, 1);
+    }
+
+    #[test]
+    fn master_twenty_g() {
+        let mut game = game(Mode::Master, Challenge::None);
+        game.level = 500;
+        assert!(!game.tetromino.is_grounded());
+        game.tick();
+        assert!(game.tetromino.is_grounded());
+    }
+
+    #[test]
+    fn master_level_stop() {
+        let mut game = game(Mode::Master, Challenge::None);
+        game.level = 98;
+        game.spawn_tetromino();
+        assert_eq!(game.level, 99);
+        game.spawn_tetromino();
+        assert_eq!(game.level, 99);
+        game.advance_level(2);
+        assert_eq!(game.level, 101);
+    }
+
+    #[test]
+    fn master_section_times() {
+        let mut game = game(Mode::Master, Challenge::None);
+        game.level = 99;
+        game.frame = 500;
+        game.advance_level(1);
+        assert_eq!(game.section_times, vec![500]);
+        game.frame = 800;
+        assert_eq!(game.section_time(), 300);
+    }
+
+    #[test]
+    fn master_completion() {
+        let mut game = game(Mode::Master, Challenge::None);
+        game.level = 998;
+        game.advance_level(4);
+        assert_eq!(game.level, MASTER_MAX_LEVEL);
+        assert!(game.over);
+    }
+
+    fn land(game: &mut Game) {
+        game.tetromino.move_all_the_way_down();
+        game.lock_tetromino();
+    }
+
+    #[test]
+    fn invisible_stack() {
+        let mut game = game(Mode::Classic, Challenge::Invisible);
+        game.frame = 10;
+        land(&mut game);
+        assert_ne!(game.grid, Game::create_grid());
+        assert_eq!(game.visible_grid(), Game::create_grid());
+        assert!(game
+            .grid
+            .iter()
+            .flatten()
+            .any(|block| block.locked_at == 10));
+
+        game.over = true;
+        assert_eq!(game.visible_grid(), game.grid);
+    }
+
+    #[test]
+    fn fading_stack() {
+        let mut game = game(Mode::Classic, Challenge::Fading);
+        land(&mut game);
+        assert_eq!(game.visible_grid(), game.grid);
+        game.frame = FADE_FRAMES;
+        assert_eq!(game.visible_grid(), Game::create_grid());
+    }
+
+    #[test]
+    fn invisible_stack_revealed_on_clear() {
+        let mut game = game(Mode::Classic, Challenge::Invisible);
+        let last_row = PLAYGROUND_HEIGHT as usize - 1;
+        game.grid[last_row] = [Block::new(1, None); PLAYGROUND_WIDTH as usize];
+        game.tetromino.grid = game.grid;
+        land(&mut game);
+        assert_eq!(game.visible_grid(), game.grid);
+        game.frame = REVEAL_FRAMES;
+        assert_eq!(game.visible_grid(), Game::create_grid());
+    }
+
+    #[test]
+    fn classic_has_no_levels() {
+        let mut game = game(Mode::Classic, Challenge::None);
+        game.spawn_tetromino();
+        assert_eq!(game.level, 0);
+    }
+
+    #[test]
+    fn big_lock() {
+        let mut game = Game::new(Settings {
+            big: true,
+            ..Settings::default()
+        });
+        land(&mut game);
+        let blocks = game.grid.iter().flatten().filter(|block| block.value != 0);
+        assert_eq!(blocks.count(), 16);
+    }
+
+    #[test]
+    fn big_line_clear() {
+        let mut game = Game::new(Settings {
+            big: true,
+            ..Settings::default()
+        });
+        let last_row = PLAYGROUND_HEIGHT as usize - 1;
+        for row in last_row - 1..=last_row {
+            game.grid[row] = [Block::new(1, None); PLAYGROUND_WIDTH as usize];
+        }
+        game.tetromino.grid = game.grid;
+        land(&mut game);
+        assert_eq!(game.score, Mode::Classic.score(0, 1, 1));
+    }
+
+    fn puzzle(name: &str) -> Puzzle {
+        let path = format!("puzzles/{}.txt", name);
+        Puzzle::load(&path).unwrap()
+    }
+
+    #[test]
+    fn load_puzzle() {
+        let mut game = game(Mode::Puzzle, Challenge::None);
+        let puzzle = puzzle("boxes");
+        game.load_puzzle(puzzle.clone());
+        assert_eq!(game.grid, puzzle.board);
+        assert_eq!(game.tetromino.grid, puzzle.board);
+        assert_eq!(game.tetromino.shape, Shape::O);
+        assert_eq!(game.next_tetromino.shape, Shape::O);
+        assert!(game.has_next());
+    }
+
+    #[test]
+    fn puzzle_solved() {
+        let mut game = game(Mode::Puzzle, Challenge::None);
+        game.load_puzzle(puzzle("tetris"));
+        assert!(!game.has_next());
+        for _ in 0..3 {
+            game.tetromino.move_sideways(Direction::Right).unwrap();
+        }
+        land(&mut game);
+        assert!(game.over);
+        assert!(game.solved);
+        assert_eq!(game.lines, 4);
+    }
+
+    #[test]
+    fn puzzle_failed() {
+        let mut game = game(Mode::Puzzle, Challenge::None);
+        game.load_puzzle(puzzle("tetris"));
+        land(&mut game);
+        assert!(game.over);
+        assert!(!game.solved);
+    }
+
+    #[test]
+    fn puzzle_perfect_clear() {
+        let mut game = game(Mode::Puzzle, Challenge::None);
+        game.load_puzzle(puzzle("perfect-clear"));
+        for _ in 0..2 {
+            game.tetromino.move_sideways(Direction::Right).unwrap();
+        }
+        land(&mut game);
+        assert!(game.solved);
+        assert_eq!(game.grid, Game::create_grid());
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut game = game(Mode::Classic, Challenge::None);
+        let grid = game.grid;
+        let shape = game.tetromino.shape;
+        let next_shape = game.next_tetromino.shape;
+        land(&mut game);
+        let landed = game.grid;
+        let following_shape = game.next_tetromino.shape;
+        assert_eq!(game.pieces, 1);
+
+        assert_eq!(game.undo(), Ok(()));
+        assert_eq!(game.grid, grid);
+        assert_eq!(game.tetromino.shape, shape);
+        assert_eq!(game.tetromino.grid, grid);
+        assert_eq!(game.next_tetromino.shape, next_shape);
+        assert_eq!(game.pieces, 0);
+        assert!(game.used_undo);
+        assert!(!game.is_high_score_eligible());
+        game.used_undo = false;
+        game.used_hint = true;
+        assert!(!game.is_high_score_eligible());
+        game.used_hint = false;
+        assert!(game.is_high_score_eligible());
+        game.used_undo = true;
+        assert_eq!(game.undo(), Err("Nothing to undo."));
+
+        assert_eq!(game.redo(), Ok(()));
+        assert_eq!(game.grid, landed);
+        assert_eq!(game.next_tetromino.shape, following_shape);
+        assert_eq!(game.pieces, 1);
+        assert_eq!(game.redo(), Err("Nothing to redo."));
+    }
+
+    #[test]
+    fn undo_restores_rng() {
+        let mut game = game(Mode::Classic, Challenge::None);
+        land(&mut game);
+        let following_shape = game.next_tetromino.shape;
+        game.undo().unwrap();
+        land(&mut game);
+        assert_eq!(game.next_tetromino.shape, following_shape);
+    }
+
+    #[test]
+    fn undo_across_section() {
+        let mut game = game(Mode::Master, Challenge::None);
+        game.level = 99;
+        game.frame = 500;
+        // Clears the bottom row to reach level 100.
+        game.tetromino.move_all_the_way_down();
+        let cells = game.tetromino.cells();
+        let y = PLAYGROUND_HEIGHT - 1;
+        for x in 0..PLAYGROUND_WIDTH {
+            if !cells.contains(&Coord { y, x }) {
+                game.grid[y as usize][x as usize] = Block::new(1, None);
+            }
+        }
+        game.lock_tetromino();
+        assert_eq!(game.level, 101);
+        assert_eq!(game.section_times, vec![500]);
+        assert!(game.reveal_until > 500);
+
+        game.undo().unwrap();
+        assert_eq!(game.level, 99);
+        assert!(game.section_times.is_empty());
+        assert_eq!(game.reveal_until, 0);
+        game.frame = 800;
+        assert_eq!(game.section_time(), 800);
+
+        game.redo().unwrap();
+        assert_eq!(game.section_times, vec![500]);
+        assert_eq!(game.section_time(), 300);
+    }
+
+    #[test]
+    fn lock_clears_redo() {
+        let mut game = game(Mode::Classic, Challenge::None);
+        land(&mut game);
+        game.undo().unwrap();
+        land(&mut game);
+        assert_eq!(game.redo(), Err("Nothing to redo."));
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut game = game(Mode::Classic, Challenge::None);
+        for _ in 0..HISTORY_LENGTH + 5 {
+            game.lock_tetromino();
+            game.over = false;
+            game.grid = Game::create_grid();
+            game.tetromino.grid = game.grid;
+        }
+        for _ in 0..HISTORY_LENGTH {
+            assert_eq!(game.undo(), Ok(()));
+        }
+        assert_eq!(game.undo(), Err("Nothing to undo."));
+    }
+
+    #[test]
+    fn same_seed_same_pieces() {
+        let settings = Settings::default();
+        let mut a = Game::with_seed(settings, 7);
+        let mut b = Game::with_seed(settings, 7);
+        for _ in 0..10 {
+            assert_eq!(a.tetromino.shape, b.tetromino.shape);
+            assert_eq!(a.tetromino.current_rotation, b.tetromino.current_rotation);
+            land(&mut a);
+            land(&mut b);
+        }
+    }
+
+    #[test]
+    fn apply_records_inputs() {
+        let mut game = game(Mode::Classic, Challenge::None);
+        game.frame = 3;
+        let x = game.tetromino.topleft.x;
+        assert_eq!(game.apply(Action::MoveLeft), Ok(()));
+        assert_eq!(game.tetromino.topleft.x, x - 1);
+        assert_eq!(game.apply(Action::Undo), Err("Nothing to undo."));
+        assert_eq!(
+            game.inputs,
+            vec![
+                Input {
+                    frame: 3,
+                    action: Action::MoveLeft
+                },
+                Input {
+                    frame: 3,
+                    action: Action::Undo
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_during_entry_delay() {
+        let mut game = game(Mode::Master, Challenge::None);
+        game.entry_delay = 1;
+        assert_eq!(game.apply(Action::HardDrop), Err("Entry delay."));
+        assert!(!game.tetromino.is_grounded());
+    }
+
+    #[test]
+    fn parse_action() {
+        assert_eq!("rotate-left".parse::<Action>(), Ok(Action::RotateLeft));
+        assert_eq!(Action::HardDrop.to_string(), "hard-drop");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut grid = Game::create_grid();
+        grid[15][0] = Block {
+            locked_at: 42,
+            ..Block::new(1, Some(Color::Red))
+        };
+        let json = serde_json::to_string(&grid).unwrap();
+        assert_eq!(serde_json::from_str::<Grid>(&json).unwrap(), grid);
+
+        let json = serde_json::to_string(&grid[15][0]).unwrap();
+        assert_eq!(serde_json::from_str::<Block>(&json).unwrap(), grid[15][0]);
+
+        let coord = Coord { y: -1, x: 7 };
+        let json = serde_json::to_string(&coord).unwrap();
+        assert_eq!(serde_json::from_str::<Coord>(&json).unwrap(), coord);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip_game() {
+        let mut game = game(Mode::Master, Challenge::Fading);
+        for action in [Action::MoveLeft, Action::HardDrop, Action::HardDrop].iter() {
+            game.apply(*action).ok();
+            for _ in 0..30 {
+                game.tick();
+            }
+        }
+        assert!(game.pieces > 0);
+        let json = serde_json::to_string(&game).unwrap();
+        let mut loaded = serde_json::from_str::<Game>(&json).unwrap();
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+
+        game.apply(Action::Undo).unwrap();
+        loaded.apply(Action::Undo).unwrap();
+        for _ in 0..300 {
+            game.tick();
+            loaded.tick();
+        }
+        assert_eq!(
+            serde_json::to_string(&loaded).unwrap(),
+            serde_json::to_string(&game).unwrap()
+        );
+    }
+
+    #[test]
+    fn hold() {
+        let mut game = game(Mode::Classic, Challenge::None);
+        let first = game.tetromino.shape;
+        let second = game.next_tetromino.shape;
+        assert_eq!(game.hold(), None);
+
+        game.apply(Action::MoveLeft).ok();
+        assert_eq!(game.apply(Action::Hold), Ok(()));
+        assert_eq!(game.hold(), Some(first));
+        assert_eq!(game.piece().shape, second);
+        assert_eq!(game.piece().topleft, Tetromino::spawn_position(1));
+        assert_eq!(game.apply(Action::Hold), Err("Hold already used."));
+
+        land(&mut game);
+        assert_eq!(game.apply(Action::Hold), Ok(()));
+        assert_eq!(game.piece().shape, first);
+        assert_eq!(
+            game.piece().current_rotation,
+            first.get_possible_rotations()[0]
+        );
+
+        game.apply(Action::Undo).unwrap();
+        assert_eq!(game.hold(), Some(first));
+        assert_eq!(game.piece().shape, second);
+    }
+
+    #[test]
+    fn hold_in_puzzle() {
+        let mut game = game(Mode::Puzzle, Challenge::None);
+        game.load_puzzle(puzzle("boxes"));
+        assert_eq!(game.apply(Action::Hold), Err("No hold in puzzles."));
+    }
+
+    #[test]
+    fn queue() {
+        let mut game = game(Mode::Classic, Challenge::None);
+        assert_eq!(game.queue(), vec![game.next_tetromino.shape]);
+
+        game.load_puzzle(puzzle("boxes"));
+        assert_eq!(game.queue(), vec![Shape::O; 3]);
+        land(&mut game);
+        assert_eq!(game.queue(), vec![Shape::O; 2]);
+        land(&mut game);
+        land(&mut game);
+        assert_eq!(game.queue(), vec![]);
+    }
+
+    #[test]
+    fn set_position() {
+        let mut game = game(Mode::Classic, Challenge::None);
+        let mut board = Game::create_grid();
+        board[15][0] = Block::new(1, Some(Shape::L.get_color()));
+        let piece = Tetromino::from_shape(Game::create_grid(), 1, Shape::T, 114);
+        game.set_position(board, piece, vec![Shape::S, Shape::Z], Some(Shape::I));
+        assert_eq!(*game.board(), board);
+        assert_eq!(game.piece().shape, Shape::T);
+        assert_eq!(game.piece().grid, board);
+        assert_eq!(game.queue()[1..], [Shape::S, Shape::Z]);
+        assert_eq!(game.hold(), Some(Shape::I));
+    }
+
+    #[test]
+    fn state() {
+        let mut game = game(Mode::Master, Challenge::None);
+        assert_eq!(game.state(), State::Playing);
+        land(&mut game);
+        assert_eq!(game.state(), State::EntryDelay);
+        game.paused = true;
+        assert_eq!(game.state(), State::Paused);
+        game.over = true;
+        assert_eq!(game.state(), State::Over);
+    }
+
+    #[test]
+    fn tick() {
+        let mut game = game(Mode::Classic, Challenge::None);
+        game.apply(Action::HardDrop).unwrap();
+        assert_eq!(game.score(), 0);
+        for _ in 0..Mode::Classic.delays(0).lock {
+            game.tick();
+        }
+        assert_eq!(game.pieces, 1);
+        assert_ne!(game.board(), &Game::create_grid());
+    }
+
+    #[test]
+    fn drain_events() {
+        let mut game = game(Mode::Classic, Challenge::None);
+        let shape = game.tetromino.shape;
+        let next = game.next_tetromino.shape;
+        let next_rotation = game.next_tetromino.current_rotation;
+        game.apply(Action::RotateRight).unwrap();
+        game.apply(Action::HardDrop).unwrap();
+        let topleft = game.tetromino.topleft;
+        let cells = game.tetromino.cells();
+        game.lock_tetromino();
+
+        assert_eq!(
+            game.drain_events(),
+            vec![
+                Event::Rotated {
+                    rotation: game.history[0].tetromino.current_rotation,
+                    kick: 0
+                },
+                Event::Moved { topleft },
+                Event::Locked {
+                    shape,
+                    cells,
+                    presses: 1
+                },
+                Event::Spawned {
+                    shape: next,
+                    rotation: next_rotation
+                },
+            ]
+        );
+        assert_eq!(game.drain_events(), vec![]);
+    }
+
+    #[test]
+    fn subscribe() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut game = game(Mode::Classic, Challenge::None);
+        let seen = Rc::new(RefCell::new(vec![]));
+        let sink = Rc::clone(&seen);
+        game.subscribe(move |event| sink.borrow_mut().push(event.clone()));
+
+        game.apply(Action::Hold).unwrap();
+        assert_eq!(seen.borrow().len(), 2);
+        assert_eq!(game.drain_events(), *seen.borrow());
+
+        game.over = false;
+        game.grid[0][0] = Block::new(1, None);
+        game.tetromino.topleft.y = 0;
+        game.lock_tetromino();
+        assert_eq!(seen.borrow().last(), Some(&Event::GameOver));
+    }
+
+    #[test]
+    fn add_garbage() {
+        let mut game = game(Mode::Classic, Challenge::None);
+        game.grid[15][0] = Block::new(1, Some(Color::Red));
+        game.add_garbage(2, 3);
+        assert_eq!(game.grid[13][0].color, Some(Color::Red));
+        for row in &game.grid[14..] {
+            let holes = row.iter().filter(|block| block.value == 0).count();
+            assert_eq!((holes, row[3].value), (1, 0));
+        }
+        assert_eq!(game.tetromino.grid, game.grid);
+        assert_eq!(
+            game.drain_events(),
+            vec![Event::GarbageReceived { lines: 2 }]
+        );
+        assert!(!game.over);
+
+        game.add_garbage(14, 3);
+        assert!(game.over);
+    }
+
+    #[test]
+    fn lines_cleared_event() {
+        let mut game = game(Mode::Classic, Challenge::None);
+        let mut dropped = game.tetromino.clone();
+        dropped.move_all_the_way_down();
+        let bottom = PLAYGROUND_HEIGHT - 1;
+        for x in 0..PLAYGROUND_WIDTH {
+            if !dropped.cells().contains(&Coord { y: bottom, x }) {
+                game.grid[bottom as usize][x as usize] = Block::new(1, None);
+            }
+        }
+        game.tetromino.grid = game.grid;
+        land(&mut game);
+
+        assert!(game.drain_events().contains(&Event::LinesCleared {
+            rows: vec![bottom as usize],
+            clear: ClearType::Single
+        }));
+    }
+
+    #[test]
+    fn t_spin_double() {
+        let mut game = game(Mode::Classic, Challenge::None);
+        let pointing_down = Shape::T
+            .get_possible_rotations()
+            .into_iter()
+            .find(|&rotation| Shape::T.to_4x4(rotation)[3] == vec![0, 0, 1, 0])
+            .unwrap();
+        let stem = 5;
+        for x in 0..PLAYGROUND_WIDTH as usize {
+            if x != stem {
+                game.grid[15][x] = Block::new(1, None);
+            }
+            if !(stem - 1..=stem + 1).contains(&x) {
+                game.grid[14][x] = Block::new(1, None);
+            }
+        }
+        game.grid[13][stem - 1] = Block::new(1, None);
+        game.tetromino = Tetromino::from_shape(game.grid, 1, Shape::T, pointing_down);
+        game.tetromino.topleft = Coord {
+            y: 12,
+            x: stem as i32 - 2,
+        };
+        game.last_rotated = true;
+        game.lock_tetromino();
+
+        assert!(game.drain_events().contains(&Event::LinesCleared {
+            rows: vec![14, 15],
+            clear: ClearType::TSpinDouble
+        }));
+    }
+
+    #[test]
+    fn level_up_event() {
+        let mut game = game(Mode::Master, Challenge::None);
+        land(&mut game);
+        assert!(game.drain_events().contains(&Event::LevelUp { level: 1 }));
+    }
 }
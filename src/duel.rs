@@ -0,0 +1,129 @@
+use ncurses as nc;
+use std::time::{Duration, Instant};
+use tetris_rs::ai::Player;
+use tetris_rs::core::{Action, FRAMES_PER_SECOND};
+use tetris_rs::ui;
+use tetris_rs::versus::Versus;
+
+const KEY_A: i32 = b'a' as i32;
+const KEY_D: i32 = b'd' as i32;
+const KEY_S: i32 = b's' as i32;
+const KEY_W: i32 = b'w' as i32;
+const KEY_Q: i32 = b'q' as i32;
+const KEY_E: i32 = b'e' as i32;
+const KEY_C: i32 = b'c' as i32;
+const KEY_COMMA: i32 = b',' as i32;
+const KEY_PERIOD: i32 = b'.' as i32;
+const KEY_SLASH: i32 = b'/' as i32;
+const KEY_P: i32 = b'p' as i32;
+const ESC: i32 = 27;
+
+/// Plays a versus game, each player on their own side of the keyboard, or
+/// against the bot on the right with the usual keys, undo and redo aside.
+///
+/// - left player: `a`, `d` move, `s` soft drop, `w` hard drop, `q`, `e`
+///   rotate, `c` hold
+/// - right player: left, right move, down soft drop, up hard drop, `,`, `.`
+///   rotate, `/` hold
+/// - `p`: pause
+/// - ESC: quit
+pub fn run(
+    versus: &mut Versus,
+    mut bot: Option<&mut dyn Player>,
+    screens: &[(nc::WINDOW, nc::WINDOW)],
+) -> Result<(), String> {
+    let names = if bot.is_some() {
+        ["YOU", "BOT"]
+    } else {
+        ["PLAYER 1", "PLAYER 2"]
+    };
+    let (_, first_screen) = screens[0];
+    let frame_duration = Duration::from_secs(1) / FRAMES_PER_SECOND;
+    nc::wtimeout(first_screen, frame_duration.as_millis() as i32);
+    let mut next_frame = Instant::now();
+    let mut paused = false;
+
+    while !versus.is_over() {
+        draw(versus, &names, screens);
+
+        // Both players may have pressed keys since the last frame.
+        let mut keys = vec![nc::wgetch(first_screen)];
+        keys.extend(std::iter::from_fn(|| {
+            Some(nc::getch()).filter(|&key| key != nc::ERR)
+        }));
+        if keys.contains(&ESC) {
+            return Ok(());
+        }
+        if keys.contains(&KEY_P) {
+            paused = !paused;
+        }
+        if paused {
+            next_frame = Instant::now();
+            continue;
+        }
+        for key in keys {
+            let action = match bot {
+                Some(_) => crate::action(key)
+                    .filter(|action| !matches!(action, Action::Undo | Action::Redo))
+                    .map(|action| (0, action)),
+                None => action(key),
+            };
+            if let Some((player, action)) = action {
+                versus.games[player].apply(action).ok();
+            }
+        }
+
+        while next_frame <= Instant::now() && !versus.is_over() {
+            if let Some(bot) = bot.as_mut() {
+                bot.step(&mut versus.games[1])?;
+            }
+            versus.tick();
+            next_frame += frame_duration;
+        }
+    }
+
+    draw(versus, &names, screens);
+    for (player, &(_, inner_screen)) in screens.iter().enumerate() {
+        let message = match versus.winner() {
+            Some(winner) if winner == player => "WINNER",
+            Some(_) => "TOPPED OUT",
+            None => "DRAW",
+        };
+        ui::draw_game_over(inner_screen, message);
+    }
+    ui::refresh_screens(first_screen);
+    nc::wtimeout(first_screen, -1);
+    nc::wgetch(first_screen);
+    Ok(())
+}
+
+fn draw(versus: &Versus, names: &[&str; 2], screens: &[(nc::WINDOW, nc::WINDOW)]) {
+    let (first_border, first_screen) = screens[0];
+    ui::erase_screens(first_screen, first_border);
+    for (player, &(border_screen, inner_screen)) in screens.iter().enumerate() {
+        ui::draw_board(&versus.games[player], None, border_screen, inner_screen);
+        ui::draw_versus(inner_screen, names[player], versus.pending[player]);
+    }
+    ui::refresh_screens(first_screen);
+}
+
+/// The player a key belongs to and what it does.
+fn action(key: i32) -> Option<(usize, Action)> {
+    match key {
+        KEY_A => Some((0, Action::MoveLeft)),
+        KEY_D => Some((0, Action::MoveRight)),
+        KEY_S => Some((0, Action::SoftDrop)),
+        KEY_W => Some((0, Action::HardDrop)),
+        KEY_Q => Some((0, Action::RotateLeft)),
+        KEY_E => Some((0, Action::RotateRight)),
+        KEY_C => Some((0, Action::Hold)),
+        nc::KEY_LEFT => Some((1, Action::MoveLeft)),
+        nc::KEY_RIGHT => Some((1, Action::MoveRight)),
+        nc::KEY_DOWN => Some((1, Action::SoftDrop)),
+        nc::KEY_UP => Some((1, Action::HardDrop)),
+        KEY_COMMA => Some((1, Action::RotateLeft)),
+        KEY_PERIOD => Some((1, Action::RotateRight)),
+        KEY_SLASH => Some((1, Action::Hold)),
+        _ => None,
+    }
+}
@@ -0,0 +1,132 @@
+use crate::core::Coord;
+use crate::shape::{Rotation, Shape};
+
+/// How many events `Game` keeps for `drain_events` before dropping the oldest.
+pub const EVENT_QUEUE_LENGTH: usize = 1000;
+
+/// A callback registered with `Game::subscribe`.
+pub type Subscriber = Box<dyn FnMut(&Event)>;
+
+/// Something that happened in the game, for observers such as effects,
+/// stats, sound or network sync.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Event {
+    Spawned {
+        shape: Shape,
+        rotation: Rotation,
+    },
+    /// The active piece moved, by the player or by gravity.
+    Moved {
+        topleft: Coord,
+    },
+    Rotated {
+        rotation: Rotation,
+        /// Which wall kick made the rotation fit. This rotation system has
+        /// no kicks, so it is always 0, the rotation in place.
+        kick: usize,
+    },
+    Locked {
+        shape: Shape,
+        cells: Vec<Coord>,
+        /// Moves and rotations made with the piece, for finesse.
+        presses: u32,
+    },
+    LinesCleared {
+        /// Grid rows that were cleared, from the top.
+        rows: Vec<usize>,
+        clear: ClearType,
+    },
+    /// Rows of garbage sent by an opponent pushed the stack up.
+    GarbageReceived {
+        lines: u32,
+    },
+    HoldUsed {
+        held: Shape,
+        /// Moves and rotations made with the piece before it was held.
+        presses: u32,
+    },
+    LevelUp {
+        level: u32,
+    },
+    GameOver,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ClearType {
+    Single,
+    Double,
+    Triple,
+    Tetris,
+    TSpinSingle,
+    TSpinDouble,
+    TSpinTriple,
+}
+
+impl ClearType {
+    pub fn new(lines: u32, t_spin: bool) -> Option<ClearType> {
+        match (lines, t_spin) {
+            (1, false) => Some(ClearType::Single),
+            (2, false) => Some(ClearType::Double),
+            (3, false) => Some(ClearType::Triple),
+            (4, _) => Some(ClearType::Tetris),
+            (1, true) => Some(ClearType::TSpinSingle),
+            (2, true) => Some(ClearType::TSpinDouble),
+            (3, true) => Some(ClearType::TSpinTriple),
+            _ => None,
+        }
+    }
+
+    pub fn lines(self) -> u32 {
+        match self {
+            ClearType::Single | ClearType::TSpinSingle => 1,
+            ClearType::Double | ClearType::TSpinDouble => 2,
+            ClearType::Triple | ClearType::TSpinTriple => 3,
+            ClearType::Tetris => 4,
+        }
+    }
+
+    /// Lines of garbage the clear sends to an opponent.
+    pub fn attack(self) -> u32 {
+        match self {
+            ClearType::Single => 0,
+            ClearType::Double => 1,
+            ClearType::Triple | ClearType::TSpinSingle => 2,
+            ClearType::Tetris | ClearType::TSpinDouble => 4,
+            ClearType::TSpinTriple => 6,
+        }
+    }
+
+    pub fn is_t_spin(self) -> bool {
+        matches!(
+            self,
+            ClearType::TSpinSingle | ClearType::TSpinDouble | ClearType::TSpinTriple
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest(
+        lines,
+        t_spin,
+        clear,
+        case(0, false, None),
+        case(0, true, None),
+        case(1, false, Some(ClearType::Single)),
+        case(2, true, Some(ClearType::TSpinDouble)),
+        case(3, true, Some(ClearType::TSpinTriple)),
+        case(4, false, Some(ClearType::Tetris))
+    )]
+    fn new_clear_type(lines: u32, t_spin: bool, clear: Option<ClearType>) {
+        assert_eq!(ClearType::new(lines, t_spin), clear);
+        if let Some(clear) = clear {
+            assert_eq!(clear.lines(), lines);
+            assert_eq!(clear.is_t_spin(), t_spin);
+        }
+    }
+}
@@ -0,0 +1,369 @@
+use crate::ai;
+use crate::core::{Action, Coord, Game};
+use crate::event::Event;
+use crate::movegen::{self, MOVES};
+use crate::shape::{Rotation, Shape};
+use crate::tetromino::Tetromino;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// Whether the action is a key press finesse counts. Drops are free, as every
+/// placement takes one and gravity does the same.
+fn counts(action: Action) -> bool {
+    matches!(
+        action,
+        Action::MoveLeft
+            | Action::MoveRight
+            | Action::RotateLeft
+            | Action::RotateRight
+            | Action::Rotate180
+    )
+}
+
+/// The fewest moves and rotations that bring the tetromino from where it is
+/// to each position it can reach.
+pub fn presses(tetromino: &Tetromino) -> HashMap<(Coord, Rotation), u32> {
+    let mut piece = tetromino.clone();
+    let start = (tetromino.topleft, tetromino.current_rotation);
+    let mut presses = HashMap::new();
+    presses.insert(start, 0);
+    // Free drops go to the front, so positions come out cheapest first.
+    let mut queue = VecDeque::new();
+    queue.push_back((start, 0));
+    while let Some((position, cost)) = queue.pop_front() {
+        if presses[&position] < cost {
+            continue;
+        }
+        for &action in MOVES.iter() {
+            movegen::place(&mut piece, position);
+            if movegen::apply(&mut piece, action).is_err() {
+                continue;
+            }
+            let next = (piece.topleft, piece.current_rotation);
+            let next_cost = cost + counts(action) as u32;
+            if presses.get(&next).is_some_and(|&known| known <= next_cost) {
+                continue;
+            }
+            presses.insert(next, next_cost);
+            if counts(action) {
+                queue.push_back((next, next_cost));
+            } else {
+                queue.push_front((next, next_cost));
+            }
+        }
+    }
+    presses
+}
+
+/// The fewest moves and rotations that put the piece, from where it is, on
+/// the cells, if it can get there at all.
+pub fn minimum(piece: &Tetromino, cells: &[Coord]) -> Option<u32> {
+    let mut piece = piece.clone();
+    let mut cells = cells.to_vec();
+    cells.sort_by_key(|cell| (cell.y, cell.x));
+    presses(&piece.clone())
+        .into_iter()
+        .filter(|&(position, _)| {
+            movegen::place(&mut piece, position);
+            let mut covered = piece.cells();
+            covered.sort_by_key(|cell| (cell.y, cell.x));
+            covered == cells
+        })
+        .map(|(_, cost)| cost)
+        .min()
+}
+
+/// How one placement compares to the fewest presses that reach it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Judgement {
+    /// The piece number, counting from 1.
+    pub piece: u32,
+    pub shape: Shape,
+    pub presses: u32,
+    pub minimum: u32,
+}
+
+impl Judgement {
+    pub fn wasted(&self) -> u32 {
+        self.presses.saturating_sub(self.minimum)
+    }
+
+    /// Whether the placement took more presses than it had to.
+    pub fn is_fault(&self) -> bool {
+        self.wasted() > 0
+    }
+}
+
+/// Judges every placement of a game as it locks. Feed it the game's events.
+#[derive(Clone)]
+pub struct Finesse {
+    /// The active piece as it spawned, on the board it is placed on.
+    spawned: Tetromino,
+    pub judgements: Vec<Judgement>,
+}
+
+impl Finesse {
+    pub fn new(game: &Game) -> Finesse {
+        Finesse {
+            spawned: spawned(game),
+            judgements: vec![],
+        }
+    }
+
+    pub fn observe(&mut self, event: &Event) {
+        match event {
+            Event::Spawned { shape, rotation } => {
+                let (grid, scale) = (self.spawned.grid, self.spawned.scale);
+                self.spawned = Tetromino::from_shape(grid, scale, *shape, *rotation);
+            }
+            Event::Locked { cells, presses, .. } => {
+                if let Some(minimum) = minimum(&self.spawned, cells) {
+                    self.judgements.push(Judgement {
+                        piece: self.judgements.len() as u32 + 1,
+                        shape: self.spawned.shape,
+                        presses: *presses,
+                        minimum,
+                    });
+                }
+                let spawned = &mut self.spawned;
+                spawned.grid = ai::lock(&spawned.grid, spawned.scale, cells).0;
+            }
+            _ => {}
+        }
+    }
+
+    /// Catches up with the game after an undo or redo, forgetting the
+    /// placements taken back.
+    pub fn sync(&mut self, game: &Game) {
+        self.spawned = spawned(game);
+        self.judgements.truncate(game.pieces as usize);
+    }
+
+    pub fn faults(&self) -> usize {
+        self.judgements
+            .iter()
+            .filter(|judgement| judgement.is_fault())
+            .count()
+    }
+
+    pub fn wasted(&self) -> u32 {
+        self.judgements.iter().map(Judgement::wasted).sum()
+    }
+}
+
+/// The end-of-game summary.
+impl fmt::Display for Finesse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "finesse: {} faults in {} pieces, {} presses wasted",
+            self.faults(),
+            self.judgements.len(),
+            self.wasted()
+        )?;
+        let mut by_shape = vec![];
+        for shape in [
+            Shape::I,
+            Shape::O,
+            Shape::T,
+            Shape::S,
+            Shape::Z,
+            Shape::J,
+            Shape::L,
+        ] {
+            let faults = self
+                .judgements
+                .iter()
+                .filter(|judgement| judgement.shape == shape && judgement.is_fault())
+                .count();
+            if faults > 0 {
+                by_shape.push(format!("{:?} {}", shape, faults));
+            }
+        }
+        if !by_shape.is_empty() {
+            write!(f, " ({})", by_shape.join(", "))?;
+        }
+        writeln!(f)
+    }
+}
+
+/// The active piece moved back to where pieces spawn, which is where it is
+/// right after spawning or an undo.
+fn spawned(game: &Game) -> Tetromino {
+    let mut piece = game.piece().clone();
+    piece.topleft = Tetromino::spawn_position(piece.scale);
+    piece
+}
+
+/// Only lets through moves and rotations that are on a shortest way to
+/// wherever they lead, so every placement is made with the fewest presses.
+/// Feed it the game's events.
+#[derive(Clone, Debug)]
+pub struct Drill {
+    /// The shape and rotation the active piece spawned with.
+    spawned: (Shape, Rotation),
+    /// The presses to each position of the active piece, once worked out.
+    presses: Option<HashMap<(Coord, Rotation), u32>>,
+    /// Presses turned down so far.
+    pub rejected: u32,
+}
+
+impl Drill {
+    pub fn new(game: &Game) -> Drill {
+        let piece = game.piece();
+        Drill {
+            spawned: (piece.shape, piece.current_rotation),
+            presses: None,
+            rejected: 0,
+        }
+    }
+
+    pub fn observe(&mut self, event: &Event) {
+        if let Event::Spawned { shape, rotation } = event {
+            self.spawned = (*shape, *rotation);
+            self.presses = None;
+        }
+    }
+
+    /// Catches up with the game after an undo or redo.
+    pub fn sync(&mut self, game: &Game) {
+        *self = Drill {
+            rejected: self.rejected,
+            ..Drill::new(game)
+        };
+    }
+
+    /// Whether the action may be applied to the game now. Drops, holds, undos
+    /// and redos always may.
+    pub fn allows(&mut self, game: &Game, action: Action) -> bool {
+        if !counts(action) {
+            return true;
+        }
+        let active = game.piece();
+        let (shape, rotation) = self.spawned;
+        let presses = self.presses.get_or_insert_with(|| {
+            presses(&Tetromino::from_shape(
+                *game.board(),
+                active.scale,
+                shape,
+                rotation,
+            ))
+        });
+
+        let mut moved = active.clone();
+        let allowed = movegen::apply(&mut moved, action).is_ok()
+            && match presses.get(&(active.topleft, active.current_rotation)) {
+                Some(&before) => {
+                    presses.get(&(moved.topleft, moved.current_rotation)) == Some(&(before + 1))
+                }
+                None => true,
+            };
+        if !allowed {
+            self.rejected += 1;
+        }
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::Settings;
+    use rstest::rstest;
+
+    fn spawn(shape: Shape) -> Tetromino {
+        Tetromino::from_shape(
+            Game::create_grid(),
+            1,
+            shape,
+            shape.get_possible_rotations()[0],
+        )
+    }
+
+    fn cells_after(shape: Shape, actions: &[Action]) -> Vec<Coord> {
+        let mut piece = spawn(shape);
+        for action in actions {
+            movegen::apply(&mut piece, *action).unwrap();
+        }
+        piece.move_all_the_way_down();
+        piece.cells()
+    }
+
+    #[rstest(
+        shape,
+        actions,
+        expected,
+        case(Shape::T, vec![], 0),
+        case(Shape::T, vec![Action::MoveLeft, Action::MoveRight], 0),
+        case(Shape::T, vec![Action::MoveLeft; 3], 3),
+        case(Shape::T, vec![Action::RotateRight, Action::RotateRight], 1),
+        case(Shape::O, vec![Action::MoveLeft; 4], 4),
+        case(Shape::I, vec![Action::RotateLeft, Action::RotateRight, Action::RotateLeft], 1)
+    )]
+    fn minimum_presses(shape: Shape, actions: Vec<Action>, expected: u32) {
+        let cells = cells_after(shape, &actions);
+        assert_eq!(minimum(&spawn(shape), &cells), Some(expected));
+    }
+
+    #[test]
+    fn judges_locked_pieces() {
+        let mut game = Game::with_seed(Settings::default(), 2);
+        let mut finesse = Finesse::new(&game);
+        let shape = game.piece().shape;
+        for action in [
+            Action::MoveLeft,
+            Action::MoveRight,
+            Action::MoveLeft,
+            Action::HardDrop,
+        ] {
+            game.apply(action).unwrap();
+        }
+        while game.pieces == 0 {
+            game.tick();
+        }
+        for event in game.drain_events() {
+            finesse.observe(&event);
+        }
+        assert_eq!(
+            finesse.judgements,
+            vec![Judgement {
+                piece: 1,
+                shape,
+                presses: 3,
+                minimum: 1
+            }]
+        );
+        assert_eq!(finesse.faults(), 1);
+        assert_eq!(finesse.wasted(), 2);
+        assert_eq!(
+            finesse.to_string(),
+            format!(
+                "finesse: 1 faults in 1 pieces, 2 presses wasted ({:?} 1)\n",
+                shape
+            )
+        );
+
+        game.apply(Action::Undo).unwrap();
+        finesse.sync(&game);
+        assert!(finesse.judgements.is_empty());
+        assert_eq!(finesse.spawned.grid, Game::create_grid());
+    }
+
+    #[test]
+    fn drill_rejects_wasted_presses() {
+        let mut game = Game::with_seed(Settings::default(), 2);
+        let mut drill = Drill::new(&game);
+        assert!(drill.allows(&game, Action::MoveLeft));
+        game.apply(Action::MoveLeft).unwrap();
+        assert!(!drill.allows(&game, Action::MoveRight));
+        assert!(drill.allows(&game, Action::MoveLeft));
+        assert!(drill.allows(&game, Action::HardDrop));
+        assert!(drill.allows(&game, Action::SoftDrop));
+        assert_eq!(drill.rejected, 1);
+
+        // Gravity moving the piece down does not change what is allowed.
+        game.apply(Action::SoftDrop).unwrap();
+        assert!(drill.allows(&game, Action::MoveLeft));
+        assert!(!drill.allows(&game, Action::MoveRight));
+    }
+}
@@ -0,0 +1,28 @@
+//! The game engine, its modes and file formats, and the curses drawing code
+//! the `tetris-rs` binary is built from.
+
+#[cfg(test)]
+#[allow(clippy::single_component_path_imports)]
+use rstest_reuse;
+
+pub mod ai;
+pub mod analysis;
+pub mod bench;
+pub mod cast;
+pub mod core;
+pub mod event;
+pub mod finesse;
+pub mod mode;
+pub mod movegen;
+pub mod opener;
+pub mod pc;
+pub mod puzzle;
+pub mod random;
+pub mod replay;
+pub mod save;
+pub mod shape;
+pub mod stats;
+pub mod tbp;
+pub mod tetromino;
+pub mod ui;
+pub mod versus;
@@ -1,83 +1,382 @@
-#[cfg(test)]
-use rstest_reuse;
-
-use crate::core::{Direction, Game};
 use ncurses as nc;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use tetris_rs::ai::{self, Bot, Player, ThreadedBot};
+use tetris_rs::analysis::Review;
+use tetris_rs::core::{Action, Game, State, FRAMES_PER_SECOND};
+use tetris_rs::event::Event;
+use tetris_rs::finesse::{Drill, Finesse};
+use tetris_rs::mode::Mode;
+use tetris_rs::puzzle::Puzzle;
+use tetris_rs::replay::Replay;
+use tetris_rs::stats::Stats;
+use tetris_rs::versus::{self, AttackTable, Versus};
+use tetris_rs::{bench, cast, pc, save, tbp, ui};
 
-mod core;
-mod shape;
-mod tetromino;
-mod ui;
+mod cli;
+mod duel;
+mod review;
+mod viewer;
 
 const KEY_A: i32 = b'a' as i32;
 const KEY_D: i32 = b'd' as i32;
 const KEY_S: i32 = b's' as i32;
+const KEY_W: i32 = b'w' as i32;
+const KEY_C: i32 = b'c' as i32;
+const KEY_H: i32 = b'h' as i32;
 const KEY_P: i32 = b'p' as i32;
+const KEY_U: i32 = b'u' as i32;
+const KEY_R: i32 = b'r' as i32;
+const KEY_V: i32 = b'v' as i32;
+const ENTER: i32 = b'\n' as i32;
 const ESC: i32 = 27;
 
 fn main() {
-    ui::curses_init();
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("bench") {
+        let options = or_usage(cli::parse_bench(args.skip(1)));
+        let report = bench::run(&options.config);
+        if options.json {
+            println!("{}", report.to_json());
+        } else {
+            print!("{}", report);
+        }
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("tbp") {
+        let options = or_usage(cli::parse_tbp(args.skip(1)));
+        let bot = Bot::new(options.depth, None);
+        let stdin = std::io::stdin();
+        or_exit(tbp::serve(
+            stdin.lock(),
+            std::io::stdout(),
+            "tetris-rs",
+            |position| tbp::suggest(&bot, position),
+        ));
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("pc") {
+        let options = or_usage(cli::parse_pc(args.skip(1)));
+        let puzzle = options.puzzle.map(|path| or_exit(Puzzle::load(&path)));
+        let queue = options
+            .queue
+            .or_else(|| puzzle.as_ref().map(|puzzle| puzzle.queue.clone()))
+            .unwrap();
+        let problem = pc::Problem {
+            board: puzzle.map_or_else(Game::create_grid, |puzzle| puzzle.board),
+            piece: queue[0],
+            hold: options.hold,
+            queue: queue[1..].to_vec(),
+        };
+        print!(
+            "{}",
+            or_exit(pc::solve(&problem, &options.limits).map_err(String::from))
+        );
+        return;
+    }
+
+    let options = or_usage(cli::parse(args));
+
+    let puzzle = options.puzzle.map(|path| or_exit(Puzzle::load(&path)));
+    let replay = options.replay.map(|path| or_exit(Replay::load(&path)));
+    let resumed = if options.resume {
+        let game = or_exit(save::load(&save::path()));
+        std::fs::remove_file(save::path()).ok();
+        Some(game)
+    } else {
+        None
+    };
+
+    if let (Some(replay), Some(path)) = (&replay, &options.cast) {
+        or_exit(std::fs::write(path, cast::export(replay)).map_err(|e| format!("{}: {}", path, e)));
+        return;
+    }
+    if let (Some(replay), true) = (&replay, options.report) {
+        print!("{}", Review::new(replay));
+        return;
+    }
+
+    if options.versus {
+        let table = options.attack.map_or_else(
+            || Ok(AttackTable::default()),
+            |path| AttackTable::load(&path),
+        );
+        let seed = options.seed.unwrap_or_else(rand::random);
+        let mut versus = Versus::new(options.settings, seed, or_exit(table));
+        let depth = options.depth.unwrap_or(ai::DEFAULT_DEPTH);
+        let pps = options.pps.unwrap_or(versus::DEFAULT_BOT_PPS);
+        let mut bot = options
+            .ai
+            .then(|| ThreadedBot::spawn(Bot::new(depth, Some(pps))));
+        ui::curses_init();
+        let screens = ui::create_screens(2);
+        nc::keypad(screens[0].1, true);
+        let result = duel::run(
+            &mut versus,
+            bot.as_mut().map(|bot| bot as &mut dyn Player),
+            &screens,
+        );
+        ui::curses_teardown();
+        or_exit(result);
+        return;
+    }
+
+    let mut game = match (resumed, options.seed) {
+        (Some(game), _) => game,
+        (None, Some(seed)) => Game::with_seed(options.settings, seed),
+        (None, None) => Game::new(options.settings),
+    };
+    let mut player: Option<Box<dyn Player>> = match &options.tbp {
+        Some(command) => Some(Box::new(or_exit(tbp::Client::spawn(command)))),
+        None if options.ai => {
+            let depth = options.depth.unwrap_or(ai::DEFAULT_DEPTH);
+            Some(Box::new(Bot::new(depth, options.pps)))
+        }
+        None => None,
+    };
+
+    if let (true, Some(player)) = (options.headless, &mut player) {
+        if game.settings.mode == Mode::Puzzle && game.puzzle.is_none() {
+            let puzzle = puzzle.ok_or_else(|| "headless puzzle games need --puzzle".to_string());
+            game.load_puzzle(or_exit(puzzle));
+        }
+        let stats = Rc::new(RefCell::new(Stats::new(&game)));
+        let sink = stats.clone();
+        game.subscribe(move |event| sink.borrow_mut().observe(event));
+        let pieces = options.pieces.unwrap_or(ai::DEFAULT_PIECES);
+        or_exit(ai::play(&mut game, player.as_mut(), pieces));
+        if game.is_over() {
+            println!("{}", game.outcome());
+        }
+        println!("score: {}", game.score());
+        println!("frames: {}", game.frame);
+        stats.borrow_mut().keep_time(&game);
+        print!("{}", stats.borrow());
+        if let Some(path) = options.record {
+            or_exit(Replay::record(&game).save(&path));
+        }
+        return;
+    }
 
-    let (border_screen, inner_screen) = ui::create_screens();
+    ui::curses_init();
 
-    nc::wtimeout(inner_screen, 100);
+    let (border_screen, inner_screen) = ui::create_screens(1)[0];
     nc::keypad(inner_screen, true);
 
-    let mut game = Game::new();
+    if let Some(replay) = replay {
+        if options.review {
+            review::run(&Review::new(&replay), border_screen, inner_screen);
+        } else {
+            viewer::run(&replay, border_screen, inner_screen);
+        }
+        ui::curses_teardown();
+        return;
+    }
 
-    loop {
-        ui::erase_screens(inner_screen, border_screen);
-        nc::box_(border_screen, 0, 0);
+    if game.settings.mode == Mode::Puzzle && game.puzzle.is_none() {
+        match puzzle.or_else(|| select_puzzle(border_screen, inner_screen)) {
+            Some(puzzle) => game.load_puzzle(puzzle),
+            None => {
+                ui::curses_teardown();
+                return;
+            }
+        }
+    }
 
-        ui::draw_landed_tetrominos(inner_screen, &game.grid);
-        ui::draw_tetromino(inner_screen, &game.tetromino);
-        ui::draw_next_tetromino(&game.next_tetromino);
-        ui::draw_score(game.score);
+    let frame_duration = Duration::from_secs(1) / FRAMES_PER_SECOND;
+    nc::wtimeout(inner_screen, frame_duration.as_millis() as i32);
+    let mut next_frame = Instant::now();
 
+    let mut error = None;
+    let hint_bot = Bot::default();
+    let mut hint = None;
+    let mut show_hint = false;
+    let mut finesse = (options.finesse || options.drill).then(|| Finesse::new(&game));
+    let mut drill = options.drill.then(|| Drill::new(&game));
+    let mut stats = Stats::new(&game);
+    // The pieces it took to build the opener, once built.
+    let mut built = None;
+    loop {
+        ui::draw_game(&game, hint.as_ref(), border_screen, inner_screen);
+        stats.keep_time(&game);
+        ui::draw_stats(&stats);
+        if options.distribution {
+            ui::draw_distribution(&stats);
+        }
+        if let Some(opener) = &options.opener {
+            let status = opener.status(game.board());
+            // Undoing past the last piece of the opener takes it down again.
+            built = built.filter(|&pieces| pieces <= game.pieces);
+            if built.is_none() && status.is_complete() {
+                built = Some(game.pieces);
+            }
+            if built.is_none() {
+                let template = if status.mirrored {
+                    opener.mirrored().template
+                } else {
+                    opener.template
+                };
+                ui::draw_template(inner_screen, &template, &game);
+            }
+            ui::draw_opener(&opener.name, &status, built.is_some());
+        }
+        if let Some(finesse) = &finesse {
+            let rejected = drill.as_ref().map(|drill| drill.rejected);
+            ui::draw_finesse(finesse, rejected);
+        }
         ui::refresh_screens(inner_screen);
 
         let user_input = nc::wgetch(inner_screen);
 
         if user_input == KEY_P {
-            game.paused = !game.paused;
-        }
-
-        if !game.paused {
-            game.handle_falling();
-            game.clear_rows();
-            match user_input {
-                nc::KEY_LEFT => {
-                    if game.tetromino.move_sideways(Direction::Left).is_err() {
-                        continue;
-                    }
-                }
-                nc::KEY_RIGHT => {
-                    if game.tetromino.move_sideways(Direction::Right).is_err() {
-                        continue;
-                    }
-                }
-                nc::KEY_DOWN => {
-                    if game.tetromino.move_down().is_err() {
-                        continue;
-                    }
-                }
-                KEY_A => {
-                    if game.tetromino.rotate(Direction::Left).is_err() {
-                        continue;
-                    }
-                }
-                KEY_D | nc::KEY_UP => {
-                    if game.tetromino.rotate(Direction::Right).is_err() {
-                        continue;
-                    }
-                }
-                KEY_S => {
-                    game.tetromino.move_all_the_way_down();
-                }
-                ESC => break,
-                _ => {}
+            game.toggle_pause();
+        }
+
+        if game.state() == State::Paused {
+            next_frame = Instant::now();
+            continue;
+        }
+
+        if user_input == ESC {
+            break;
+        }
+        if user_input == KEY_H {
+            show_hint = !show_hint;
+            game.used_hint = true;
+        }
+        // Keys are applied before the game moves on, so the events they
+        // follow have been seen by the finesse drill.
+        let key = action(user_input).filter(|_| player.is_none());
+        if let Some(action) = key {
+            if drill
+                .as_mut()
+                .is_none_or(|drill| drill.allows(&game, action))
+            {
+                game.apply(action).ok();
+            } else {
+                nc::beep();
+            }
+        }
+        let restored = matches!(key, Some(Action::Undo | Action::Redo));
+        if let (true, Some(finesse)) = (restored, &mut finesse) {
+            finesse.sync(&game);
+        }
+        if let (true, Some(drill)) = (restored, &mut drill) {
+            drill.sync(&game);
+        }
+
+        while next_frame <= Instant::now() {
+            if let Some(Err(message)) = player.as_mut().map(|player| player.step(&mut game)) {
+                error = Some(message);
+            }
+            game.tick();
+            next_frame += frame_duration;
+        }
+
+        let mut spawned = false;
+        for event in game.drain_events() {
+            spawned |= matches!(event, Event::Spawned { .. });
+            stats.observe(&event);
+            if let Some(finesse) = &mut finesse {
+                finesse.observe(&event);
+            }
+            if let Some(drill) = &mut drill {
+                drill.observe(&event);
             }
         }
+        if game.is_over() || error.is_some() {
+            break;
+        }
+
+        // The hint is worked out again for every new piece, and after undos
+        // and redos, which bring back a piece without spawning it.
+        if !show_hint {
+            hint = None;
+        } else if hint.is_none() || spawned || restored {
+            hint = hint_bot.best_placement(&game);
+        }
+    }
+
+    if game.is_over() {
+        ui::draw_game(&game, None, border_screen, inner_screen);
+        ui::draw_game_over(inner_screen, &format!("{}  V: REVIEW", game.outcome()));
+        ui::refresh_screens(inner_screen);
+        nc::wtimeout(inner_screen, -1);
+        if nc::wgetch(inner_screen) == KEY_V {
+            let review = Review::new(&Replay::record(&game));
+            review::run(&review, border_screen, inner_screen);
+        }
     }
     ui::curses_teardown();
+    if let Some(message) = error {
+        eprintln!("{}", message);
+    }
+    if let Some(finesse) = finesse {
+        print!("{}", finesse);
+    }
+
+    if !game.is_over() {
+        or_exit(save::save(&game, &save::path()));
+        println!("Game saved, continue it with: tetris-rs --resume");
+    }
+
+    if let Some(path) = options.record {
+        or_exit(Replay::record(&game).save(&path));
+    }
+}
+
+fn or_usage<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, cli::USAGE);
+        std::process::exit(1);
+    })
+}
+
+fn or_exit<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|message| {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    })
+}
+
+fn action(key: i32) -> Option<Action> {
+    match key {
+        nc::KEY_LEFT => Some(Action::MoveLeft),
+        nc::KEY_RIGHT => Some(Action::MoveRight),
+        nc::KEY_DOWN => Some(Action::SoftDrop),
+        KEY_S => Some(Action::HardDrop),
+        KEY_A => Some(Action::RotateLeft),
+        KEY_D | nc::KEY_UP => Some(Action::RotateRight),
+        KEY_W => Some(Action::Rotate180),
+        KEY_C => Some(Action::Hold),
+        KEY_U => Some(Action::Undo),
+        KEY_R => Some(Action::Redo),
+        _ => None,
+    }
+}
+
+fn select_puzzle(border_screen: nc::WINDOW, inner_screen: nc::WINDOW) -> Option<Puzzle> {
+    let mut puzzles = Puzzle::bundled();
+    let names = puzzles
+        .iter()
+        .map(|puzzle| puzzle.name.clone())
+        .collect::<Vec<String>>();
+    let mut selected = 0;
+
+    nc::wtimeout(inner_screen, -1);
+    loop {
+        ui::erase_screens(inner_screen, border_screen);
+        nc::box_(border_screen, 0, 0);
+        ui::draw_menu(inner_screen, "SELECT PUZZLE", &names, selected);
+        ui::refresh_screens(inner_screen);
+
+        match nc::wgetch(inner_screen) {
+            nc::KEY_UP => selected = selected.saturating_sub(1),
+            nc::KEY_DOWN => selected = (selected + 1).min(names.len() - 1),
+            ENTER | nc::KEY_ENTER => return Some(puzzles.swap_remove(selected)),
+            ESC => return None,
+            _ => {}
+        }
+    }
 }
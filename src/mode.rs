@@ -0,0 +1,333 @@
+use crate::core::FRAMES_PER_SECOND;
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// Gravity is expressed in 1/256ths of a row per frame, so `G` is one row per frame.
+pub const G: u32 = 256;
+
+pub const SECTION_LENGTH: u32 = 100;
+pub const FADE_FRAMES: u64 = 5 * FRAMES_PER_SECOND as u64;
+pub const REVEAL_FRAMES: u64 = FRAMES_PER_SECOND as u64;
+pub const MASTER_MAX_LEVEL: u32 = 999;
+
+const CLASSIC_GRAVITY: u32 = G / 30;
+const CLASSIC_DELAYS: Delays = Delays {
+    entry: 0,
+    line_entry: 0,
+    lock: 30,
+    clear: 0,
+};
+
+/// (level, gravity) pairs; gravity applies from the given level onwards.
+const MASTER_GRAVITY: [(u32, u32); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, G),
+    (300, 2 * G),
+    (330, 3 * G),
+    (360, 4 * G),
+    (400, 5 * G),
+    (420, 4 * G),
+    (450, 3 * G),
+    (500, 20 * G),
+];
+
+/// (level, delays) pairs; delays shrink with every section past 500.
+const MASTER_DELAYS: [(u32, Delays); 6] = [
+    (
+        0,
+        Delays {
+            entry: 25,
+            line_entry: 25,
+            lock: 30,
+            clear: 40,
+        },
+    ),
+    (
+        500,
+        Delays {
+            entry: 25,
+            line_entry: 25,
+            lock: 30,
+            clear: 25,
+        },
+    ),
+    (
+        600,
+        Delays {
+            entry: 25,
+            line_entry: 16,
+            lock: 30,
+            clear: 16,
+        },
+    ),
+    (
+        700,
+        Delays {
+            entry: 16,
+            line_entry: 12,
+            lock: 30,
+            clear: 12,
+        },
+    ),
+    (
+        800,
+        Delays {
+            entry: 12,
+            line_entry: 6,
+            lock: 30,
+            clear: 6,
+        },
+    ),
+    (
+        900,
+        Delays {
+            entry: 12,
+            line_entry: 6,
+            lock: 17,
+            clear: 6,
+        },
+    ),
+];
+
+/// (score, grade) pairs; the grade is awarded once the score is reached.
+const MASTER_GRADES: [(u64, &str); 18] = [
+    (0, "9"),
+    (400, "8"),
+    (800, "7"),
+    (1400, "6"),
+    (2000, "5"),
+    (3500, "4"),
+    (5500, "3"),
+    (8000, "2"),
+    (12000, "1"),
+    (16000, "S1"),
+    (22000, "S2"),
+    (30000, "S3"),
+    (40000, "S4"),
+    (52000, "S5"),
+    (66000, "S6"),
+    (82000, "S7"),
+    (100000, "S8"),
+    (120000, "S9"),
+];
+
+#[derive(Clone, Copy, Debug, Default, Display, EnumIter, EnumString, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[strum(serialize_all = "lowercase")]
+pub enum Mode {
+    #[default]
+    Classic,
+    Master,
+    Puzzle,
+}
+
+/// How the landed blocks are shown.
+#[derive(Clone, Copy, Debug, Default, Display, EnumIter, EnumString, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[strum(serialize_all = "lowercase")]
+pub enum Challenge {
+    #[default]
+    None,
+    Invisible,
+    Fading,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Settings {
+    pub mode: Mode,
+    pub challenge: Challenge,
+    /// Doubles every mino to cover 2x2 cells.
+    pub big: bool,
+}
+
+impl Settings {
+    pub fn scale(&self) -> i32 {
+        if self.big {
+            2
+        } else {
+            1
+        }
+    }
+}
+
+/// Frame counts for the pauses between pieces.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Delays {
+    pub entry: u32,
+    pub line_entry: u32,
+    pub lock: u32,
+    pub clear: u32,
+}
+
+impl Mode {
+    pub fn gravity(&self, level: u32) -> u32 {
+        match self {
+            Mode::Classic | Mode::Puzzle => CLASSIC_GRAVITY,
+            Mode::Master => lookup(&MASTER_GRAVITY, level),
+        }
+    }
+
+    pub fn delays(&self, level: u32) -> Delays {
+        match self {
+            Mode::Classic | Mode::Puzzle => CLASSIC_DELAYS,
+            Mode::Master => lookup(&MASTER_DELAYS, level),
+        }
+    }
+
+    pub fn score(&self, level: u32, lines: u32, combo: u32) -> u64 {
+        match self {
+            Mode::Classic | Mode::Puzzle => lines as u64 * 10,
+            Mode::Master => ((level + lines).div_ceil(4) * lines * combo) as u64,
+        }
+    }
+
+    pub fn grade(&self, score: u64) -> Option<&'static str> {
+        match self {
+            Mode::Classic | Mode::Puzzle => None,
+            Mode::Master => Some(lookup(&MASTER_GRADES, score)),
+        }
+    }
+
+    pub fn has_levels(&self) -> bool {
+        matches!(self, Mode::Master)
+    }
+}
+
+impl Challenge {
+    /// Whether a block locked `age` frames ago is still shown.
+    pub fn shows(&self, age: u64) -> bool {
+        match self {
+            Challenge::None => true,
+            Challenge::Invisible => false,
+            Challenge::Fading => age < FADE_FRAMES,
+        }
+    }
+}
+
+fn lookup<K: PartialOrd + Copy, V: Copy>(table: &[(K, V)], key: K) -> V {
+    table
+        .iter()
+        .rev()
+        .find(|(threshold, _)| key >= *threshold)
+        .map(|(_, value)| *value)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest(
+        mode,
+        level,
+        gravity,
+        case(Mode::Classic, 0, G / 30),
+        case(Mode::Classic, 900, G / 30),
+        case(Mode::Master, 0, 4),
+        case(Mode::Master, 29, 4),
+        case(Mode::Master, 30, 6),
+        case(Mode::Master, 200, 4),
+        case(Mode::Master, 251, G),
+        case(Mode::Master, 499, 3 * G),
+        case(Mode::Master, 500, 20 * G),
+        case(Mode::Master, 999, 20 * G)
+    )]
+    fn get_gravity(mode: Mode, level: u32, gravity: u32) {
+        assert_eq!(mode.gravity(level), gravity);
+    }
+
+    #[rstest(
+        level,
+        lock,
+        clear,
+        case(0, 30, 40),
+        case(499, 30, 40),
+        case(500, 30, 25),
+        case(650, 30, 16),
+        case(899, 30, 6),
+        case(900, 17, 6)
+    )]
+    fn master_delays(level: u32, lock: u32, clear: u32) {
+        let delays = Mode::Master.delays(level);
+        assert_eq!(delays.lock, lock);
+        assert_eq!(delays.clear, clear);
+    }
+
+    #[rstest(
+        score,
+        grade,
+        case(0, "9"),
+        case(399, "9"),
+        case(400, "8"),
+        case(16000, "S1"),
+        case(1_000_000, "S9")
+    )]
+    fn get_master_grade(score: u64, grade: &str) {
+        assert_eq!(Mode::Master.grade(score), Some(grade));
+    }
+
+    #[test]
+    fn classic_has_no_grade() {
+        assert_eq!(Mode::Classic.grade(1_000_000), None);
+    }
+
+    #[rstest(
+        mode,
+        level,
+        lines,
+        combo,
+        score,
+        case(Mode::Classic, 0, 4, 1, 40),
+        case(Mode::Master, 0, 1, 1, 1),
+        case(Mode::Master, 100, 4, 1, 104),
+        case(Mode::Master, 100, 2, 3, 156)
+    )]
+    fn get_score(mode: Mode, level: u32, lines: u32, combo: u32, score: u64) {
+        assert_eq!(mode.score(level, lines, combo), score);
+    }
+
+    #[rstest(
+        challenge,
+        age,
+        shown,
+        case(Challenge::None, 0, true),
+        case(Challenge::None, 100 * FADE_FRAMES, true),
+        case(Challenge::Invisible, 0, false),
+        case(Challenge::Fading, 0, true),
+        case(Challenge::Fading, FADE_FRAMES - 1, true),
+        case(Challenge::Fading, FADE_FRAMES, false)
+    )]
+    fn shows(challenge: Challenge, age: u64, shown: bool) {
+        assert_eq!(challenge.shows(age), shown);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("master".parse::<Mode>(), Ok(Mode::Master));
+        assert_eq!("classic".parse::<Mode>(), Ok(Mode::Classic));
+        assert!("zen".parse::<Mode>().is_err());
+        assert_eq!("fading".parse::<Challenge>(), Ok(Challenge::Fading));
+    }
+}
@@ -0,0 +1,214 @@
+use crate::core::{Action, Coord, Direction};
+use crate::shape::{Rotation, Shape};
+use crate::tetromino::Tetromino;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// The moves tried from every position, in the order placements are found.
+pub(crate) const MOVES: [Action; 7] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::RotateRight,
+    Action::RotateLeft,
+    Action::Rotate180,
+    Action::SoftDrop,
+    Action::HardDrop,
+];
+
+/// A resting position the piece can be brought to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Placement {
+    pub shape: Shape,
+    pub rotation: Rotation,
+    pub topleft: Coord,
+    /// The grid cells the piece covers, sorted.
+    pub cells: Vec<Coord>,
+    /// The fewest inputs that bring the piece there from where it is now.
+    /// The piece still has to lock on its own afterwards.
+    pub inputs: Vec<Action>,
+}
+
+/// Every position the tetromino can come to rest in through moves, rotations
+/// and drops, tucks and spins included, found with a breadth-first search so
+/// each comes with its shortest input sequence. Positions covering the same
+/// cells are only listed once. Gravity is not taken into account.
+pub fn placements(tetromino: &Tetromino) -> Vec<Placement> {
+    // Positions are searched with a single tetromino that is moved around,
+    // since cloning one copies the whole grid.
+    let mut piece = tetromino.clone();
+    let start = (tetromino.topleft, tetromino.current_rotation);
+    // Every position reached, with the one it was reached from and how.
+    let mut parents = HashMap::new();
+    parents.insert(start, None);
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+    let mut covered = HashSet::new();
+    let mut placements = vec![];
+
+    while let Some(position) = queue.pop_front() {
+        place(&mut piece, position);
+        if piece.move_down().is_err() {
+            let mut cells = piece.cells();
+            cells.sort_by_key(|cell| (cell.y, cell.x));
+            if covered.insert(cells.clone()) {
+                placements.push(Placement {
+                    shape: piece.shape,
+                    rotation: piece.current_rotation,
+                    topleft: piece.topleft,
+                    cells,
+                    inputs: path(&parents, position),
+                });
+            }
+        }
+
+        for &action in MOVES.iter() {
+            place(&mut piece, position);
+            if apply(&mut piece, action).is_err() {
+                continue;
+            }
+            let next = (piece.topleft, piece.current_rotation);
+            if parents.contains_key(&next) {
+                continue;
+            }
+            parents.insert(next, Some((position, action)));
+            queue.push_back(next);
+        }
+    }
+    placements
+}
+
+/// Where a piece is, by its top left corner and rotation.
+pub(crate) type Position = (Coord, Rotation);
+
+/// The inputs leading from the start of the search to `position`.
+fn path(
+    parents: &HashMap<Position, Option<(Position, Action)>>,
+    position: Position,
+) -> Vec<Action> {
+    let mut inputs = vec![];
+    let mut position = position;
+    while let Some((parent, action)) = parents[&position] {
+        inputs.push(action);
+        position = parent;
+    }
+    inputs.reverse();
+    inputs
+}
+
+pub(crate) fn place(tetromino: &mut Tetromino, (topleft, rotation): Position) {
+    tetromino.topleft = topleft;
+    tetromino.current_rotation = rotation;
+}
+
+pub(crate) fn apply(tetromino: &mut Tetromino, action: Action) -> Result<(), &'static str> {
+    match action {
+        Action::MoveLeft => tetromino.move_sideways(Direction::Left),
+        Action::MoveRight => tetromino.move_sideways(Direction::Right),
+        Action::SoftDrop => tetromino.move_down(),
+        Action::HardDrop => {
+            tetromino.move_all_the_way_down();
+            Ok(())
+        }
+        Action::RotateLeft => tetromino.rotate(Direction::Left),
+        Action::RotateRight => tetromino.rotate(Direction::Right),
+        Action::Rotate180 => tetromino.rotate_180(),
+        Action::Hold | Action::Undo | Action::Redo => Err("Not a move."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Block, Game, PLAYGROUND_HEIGHT, PLAYGROUND_WIDTH};
+    use rstest::rstest;
+
+    fn tetromino(shape: Shape) -> Tetromino {
+        let rotation = shape.get_possible_rotations()[0];
+        Tetromino::from_shape(Game::create_grid(), 1, shape, rotation)
+    }
+
+    /// Distinct rotations times the columns each fits in.
+    #[rstest(
+        shape,
+        count,
+        case(Shape::O, 9),
+        case(Shape::I, 7 + 10),
+        case(Shape::S, 8 + 9),
+        case(Shape::Z, 8 + 9),
+        case(Shape::J, 8 + 9 + 8 + 9),
+        case(Shape::L, 8 + 9 + 8 + 9),
+        case(Shape::T, 8 + 9 + 8 + 9)
+    )]
+    fn empty_board(shape: Shape, count: usize) {
+        let placements = placements(&tetromino(shape));
+        assert_eq!(placements.len(), count);
+        for placement in &placements {
+            assert!(placement
+                .cells
+                .iter()
+                .any(|cell| cell.y == PLAYGROUND_HEIGHT - 1));
+        }
+        assert!(placements
+            .iter()
+            .any(|placement| placement.inputs == vec![Action::HardDrop]));
+    }
+
+    #[test]
+    fn inputs_reach_placement() {
+        let mut grid = Game::create_grid();
+        for x in 0..PLAYGROUND_WIDTH as usize - 1 {
+            grid[15][x] = Block::new(1, None);
+            grid[14][x / 3 * 3] = Block::new(1, None);
+        }
+        let start = Tetromino::from_shape(grid, 1, Shape::T, Shape::T.get_possible_rotations()[0]);
+        for placement in placements(&start) {
+            let mut tetromino = start.clone();
+            for action in &placement.inputs {
+                apply(&mut tetromino, *action).unwrap();
+            }
+            let mut cells = tetromino.cells();
+            cells.sort_by_key(|cell| (cell.y, cell.x));
+            assert_eq!(cells, placement.cells);
+            assert!(tetromino.is_grounded());
+        }
+    }
+
+    #[test]
+    fn tuck_under_overhang() {
+        let mut grid = Game::create_grid();
+        for block in grid[13].iter_mut().take(6) {
+            *block = Block::new(1, None);
+        }
+        let placements = placements(&Tetromino::from_shape(
+            grid,
+            1,
+            Shape::O,
+            Shape::O.get_possible_rotations()[0],
+        ));
+
+        let tucked = placements
+            .iter()
+            .find(|placement| placement.cells.contains(&Coord { y: 15, x: 0 }))
+            .unwrap();
+        let drop = tucked
+            .inputs
+            .iter()
+            .position(|action| *action == Action::HardDrop)
+            .unwrap();
+        assert!(tucked.inputs[drop + 1..]
+            .iter()
+            .all(|action| *action == Action::MoveLeft));
+        assert!(placements
+            .iter()
+            .any(|placement| placement.cells.contains(&Coord { y: 12, x: 0 })));
+    }
+
+    #[test]
+    fn no_duplicates() {
+        let placements = placements(&tetromino(Shape::I));
+        let cells = placements
+            .iter()
+            .map(|placement| placement.cells.clone())
+            .collect::<HashSet<Vec<Coord>>>();
+        assert_eq!(cells.len(), placements.len());
+    }
+}
@@ -0,0 +1,251 @@
+use crate::core::{Block, Grid};
+use crate::puzzle;
+use crate::shape::Shape;
+use strum::IntoEnumIterator;
+
+/// The openers shipped with the game.
+pub const BUNDLED: &str = include_str!("../openers.txt");
+
+/// A stack to build at the start of a game, written as:
+///
+/// ```text
+/// name: TKI
+/// board:
+/// L..ZZ.S...
+/// L...ZZSSOO
+/// LL.IIIISOO
+/// ```
+///
+/// Several openers go in one file, separated by blank lines. Board rows are
+/// bottom-aligned and written as in puzzles.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Opener {
+    pub name: String,
+    pub template: Grid,
+}
+
+/// How a stack compares to an opener's template.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Status {
+    /// Whether the stack follows the mirrored template.
+    pub mirrored: bool,
+    /// Template cells the stack fills.
+    pub filled: usize,
+    /// Template cells in all.
+    pub total: usize,
+    /// Blocks of the stack outside the template.
+    pub stray: usize,
+}
+
+impl Status {
+    pub fn is_on_track(&self) -> bool {
+        self.stray == 0
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.is_on_track() && self.filled == self.total
+    }
+}
+
+impl Opener {
+    pub fn bundled() -> Vec<Opener> {
+        Opener::parse_all(BUNDLED).unwrap()
+    }
+
+    /// Finds a bundled opener by name, ignoring case, spaces and dashes.
+    pub fn named(name: &str) -> Result<Opener, String> {
+        let key = |name: &str| {
+            name.chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+        };
+        let openers = Opener::bundled();
+        let names = openers
+            .iter()
+            .map(|opener| opener.name.clone())
+            .collect::<Vec<String>>();
+        openers
+            .into_iter()
+            .find(|opener| key(&opener.name) == key(name))
+            .ok_or(format!(
+                "unknown opener: {} (one of {})",
+                name,
+                names.join(", ")
+            ))
+    }
+
+    /// Reads openers separated by blank lines.
+    pub fn parse_all(text: &str) -> Result<Vec<Opener>, String> {
+        let mut openers = vec![];
+        let mut block = vec![];
+        for line in text.lines().chain(Some("")) {
+            if !line.trim().is_empty() {
+                block.push(line);
+            } else if !block.is_empty() {
+                openers.push(Opener::parse(&block.join("\n"))?);
+                block.clear();
+            }
+        }
+        Ok(openers)
+    }
+
+    pub fn parse(text: &str) -> Result<Opener, String> {
+        let mut name = None;
+        let mut lines = text.lines();
+
+        for line in &mut lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or(format!("expected `key: value`, got: {}", line))?;
+            match key {
+                "name" => name = Some(value.trim().to_string()),
+                "board" => break,
+                _ => return Err(format!("unknown key: {}", key)),
+            }
+        }
+
+        let name = name.ok_or("missing name")?;
+        let rows = lines
+            .map(str::trim_end)
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<&str>>();
+        let template = puzzle::parse_board(&rows)?;
+        let blocks = filled(&template).count();
+        if blocks == 0 || !blocks.is_multiple_of(4) {
+            return Err(format!("{}: board is not made of whole pieces", name));
+        }
+        Ok(Opener { name, template })
+    }
+
+    /// The opener built the other way round.
+    pub fn mirrored(&self) -> Opener {
+        let mut template = self.template;
+        for row in template.iter_mut() {
+            row.reverse();
+            for block in row.iter_mut() {
+                let shape = Shape::iter().find(|shape| Some(shape.get_color()) == block.color);
+                if let Some(shape) = shape {
+                    block.color = Some(shape.mirrored().get_color());
+                }
+            }
+        }
+        Opener {
+            name: self.name.clone(),
+            template,
+        }
+    }
+
+    /// Compares the stack to the template and its mirror image, going by
+    /// whichever it is closer to.
+    pub fn status(&self, grid: &Grid) -> Status {
+        let straight = compare(&self.template, grid, false);
+        let mirrored = compare(&self.mirrored().template, grid, true);
+        let distance = |status: &Status| (status.stray, status.total - status.filled);
+        if distance(&mirrored) < distance(&straight) {
+            mirrored
+        } else {
+            straight
+        }
+    }
+}
+
+fn compare(template: &Grid, grid: &Grid, mirrored: bool) -> Status {
+    let mut status = Status {
+        mirrored,
+        filled: 0,
+        total: filled(template).count(),
+        stray: 0,
+    };
+    for (wanted, block) in template.iter().flatten().zip(grid.iter().flatten()) {
+        match (wanted.value != 0, block.value != 0) {
+            (true, true) => status.filled += 1,
+            (false, true) => status.stray += 1,
+            _ => {}
+        }
+    }
+    status
+}
+
+fn filled(grid: &Grid) -> impl Iterator<Item = &Block> {
+    grid.iter().flatten().filter(|block| block.value != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Game;
+
+    fn board(rows: &[&str]) -> Grid {
+        puzzle::parse_board(rows).unwrap()
+    }
+
+    #[test]
+    fn bundled_openers() {
+        let names = Opener::bundled()
+            .into_iter()
+            .map(|opener| opener.name)
+            .collect::<Vec<String>>();
+        assert_eq!(names, vec!["TKI", "DT cannon", "PCO", "MKO"]);
+        assert_eq!(Opener::named("dt-cannon").unwrap().name, "DT cannon");
+        assert_eq!(
+            Opener::named("stsd"),
+            Err("unknown opener: stsd (one of TKI, DT cannon, PCO, MKO)".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Opener::parse("name: half\nboard:\nII........"),
+            Err("half: board is not made of whole pieces".to_string())
+        );
+        assert_eq!(
+            Opener::parse("goal: lines 4"),
+            Err("unknown key: goal".to_string())
+        );
+    }
+
+    #[test]
+    fn status() {
+        let tki = Opener::named("TKI").unwrap();
+        let empty = tki.status(&Game::create_grid());
+        assert_eq!(
+            empty,
+            Status {
+                mirrored: false,
+                filled: 0,
+                total: 20,
+                stray: 0
+            }
+        );
+        assert!(empty.is_on_track() && !empty.is_complete());
+
+        let started = tki.status(&board(&["......SS..", ".....SS..."]));
+        assert_eq!((started.filled, started.stray), (4, 0));
+
+        let stray = tki.status(&board(&["........OO", "..OO....OO"]));
+        assert_eq!((stray.filled, stray.stray), (5, 1));
+        assert!(!stray.is_on_track());
+
+        assert!(tki.status(&tki.template).is_complete());
+    }
+
+    #[test]
+    fn mirrored() {
+        let tki = Opener::named("TKI").unwrap();
+        let mirrored = tki.mirrored();
+        assert_eq!(puzzle::format_cell(&mirrored.template[15][0]), 'O');
+        assert_eq!(puzzle::format_cell(&mirrored.template[15][9]), 'J');
+        assert_eq!(mirrored.mirrored(), tki);
+
+        let status = tki.status(&mirrored.template);
+        assert!(status.mirrored && status.is_complete());
+        let started = tki.status(&board(&["OO........", "OO........"]));
+        assert!(started.mirrored && started.is_on_track());
+    }
+}
@@ -0,0 +1,415 @@
+use crate::ai;
+use crate::core::{Action, Block, Coord, Game, Grid, PLAYGROUND_HEIGHT, PLAYGROUND_WIDTH};
+use crate::movegen::{placements, Placement};
+use crate::puzzle;
+use crate::shape::Shape;
+use crate::tetromino::Tetromino;
+use std::collections::HashSet;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// The most pieces a solution uses unless told otherwise, enough for a
+/// four-line perfect clear from an empty board.
+pub const DEFAULT_DEPTH: usize = 10;
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The position to find a perfect clear from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub board: Grid,
+    pub piece: Shape,
+    pub hold: Option<Shape>,
+    /// The pieces after the active one, in order.
+    pub queue: Vec<Shape>,
+}
+
+impl Problem {
+    pub fn from_game(game: &Game) -> Result<Problem, &'static str> {
+        if game.settings.big {
+            return Err("Big games have no perfect clears to solve.");
+        }
+        Ok(Problem {
+            board: *game.board(),
+            piece: game.piece().shape,
+            hold: game.hold(),
+            queue: game.queue(),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limits {
+    /// The most pieces the solution may place.
+    pub depth: usize,
+    pub timeout: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            depth: DEFAULT_DEPTH,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+/// One placement of a solution.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    /// Whether the piece is swapped with the hold first.
+    pub hold: bool,
+    /// Its inputs count from where the piece spawns.
+    pub placement: Placement,
+    /// The board before the piece is placed.
+    pub board: Grid,
+}
+
+impl Step {
+    /// The inputs for the step, the hold included.
+    pub fn inputs(&self) -> Vec<Action> {
+        let hold = if self.hold {
+            vec![Action::Hold]
+        } else {
+            vec![]
+        };
+        hold.into_iter()
+            .chain(self.placement.inputs.iter().copied())
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Solution {
+    pub steps: Vec<Step>,
+}
+
+/// Prints every step as a diagram of the board with the piece placed, the
+/// piece in lowercase.
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            let shape = step.placement.shape;
+            write!(f, "{}. {:?}", i + 1, shape)?;
+            if step.hold {
+                write!(f, " (hold)")?;
+            }
+            writeln!(f)?;
+            let letter = format!("{:?}", shape)
+                .to_lowercase()
+                .chars()
+                .next()
+                .unwrap();
+            let top = step
+                .board
+                .iter()
+                .position(|row| row.iter().any(|block| block.value != 0))
+                .into_iter()
+                .chain(step.placement.cells.iter().map(|cell| cell.y as usize))
+                .min()
+                .unwrap_or(0);
+            for (y, row) in step.board.iter().enumerate().skip(top) {
+                let row = row.iter().enumerate().map(|(x, block)| {
+                    let cell = Coord {
+                        y: y as i32,
+                        x: x as i32,
+                    };
+                    if step.placement.cells.contains(&cell) {
+                        letter
+                    } else {
+                        puzzle::format_cell(block)
+                    }
+                });
+                writeln!(f, "{}", row.collect::<String>())?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Searches for placements of the active piece and the queue, using the hold
+/// as it goes, that leave the board empty. Solutions clearing the fewest
+/// lines are found first.
+pub fn solve(problem: &Problem, limits: &Limits) -> Result<Solution, &'static str> {
+    let filled = problem
+        .board
+        .iter()
+        .flatten()
+        .filter(|block| block.value != 0)
+        .count();
+    let height = problem
+        .board
+        .iter()
+        .position(|row| row.iter().any(|block| block.value != 0))
+        .map_or(0, |y| PLAYGROUND_HEIGHT as usize - y);
+    let pieces = 1 + problem.queue.len() + problem.hold.map_or(0, |_| 1);
+    let available = pieces.min(limits.depth);
+
+    let mut sequence = vec![problem.piece];
+    sequence.extend(&problem.queue);
+    let mut search = Search {
+        sequence,
+        deadline: Instant::now() + limits.timeout,
+        failed: HashSet::new(),
+    };
+    // Every line of the cleared area takes a whole number of pieces to fill.
+    for lines in height.max(1)..PLAYGROUND_HEIGHT as usize {
+        let cells = lines * PLAYGROUND_WIDTH as usize - filled;
+        if !cells.is_multiple_of(4) {
+            continue;
+        }
+        if cells / 4 > available {
+            break;
+        }
+        search.failed.clear();
+        if let Some(mut steps) = search.run(&problem.board, lines, 0, problem.hold)? {
+            steps.reverse();
+            return Ok(Solution { steps });
+        }
+    }
+    Err("No perfect clear within the depth limit.")
+}
+
+struct Search {
+    /// The active piece followed by the queue.
+    sequence: Vec<Shape>,
+    deadline: Instant,
+    /// Boards and piece states already known not to lead to a perfect clear.
+    failed: HashSet<(Vec<u16>, usize, Option<Shape>)>,
+}
+
+impl Search {
+    /// The steps, last first, that clear the board's bottom `lines` rows with
+    /// the pieces from `next` on, if there are any.
+    fn run(
+        &mut self,
+        board: &Grid,
+        lines: usize,
+        next: usize,
+        hold: Option<Shape>,
+    ) -> Result<Option<Vec<Step>>, &'static str> {
+        if Instant::now() >= self.deadline {
+            return Err("Timed out.");
+        }
+        let Some(&current) = self.sequence.get(next) else {
+            return Ok(None);
+        };
+        // The pieces that can be played now, whether that takes the hold and
+        // what is left in the queue and the hold afterwards.
+        let mut choices = vec![(current, false, next + 1, hold)];
+        match hold {
+            Some(held) if held != current => choices.push((held, true, next + 1, Some(current))),
+            None => {
+                if let Some(&after) = self.sequence.get(next + 1) {
+                    choices.push((after, true, next + 2, Some(current)));
+                }
+            }
+            _ => {}
+        }
+
+        for (shape, held, next, hold) in choices {
+            let rotation = shape.get_possible_rotations()[0];
+            let piece = Tetromino::from_shape(*board, 1, shape, rotation);
+            let floor = PLAYGROUND_HEIGHT as usize - lines;
+            for placement in placements(&piece) {
+                if placement.cells.iter().any(|cell| (cell.y as usize) < floor) {
+                    continue;
+                }
+                let (after, cleared) = place(board, &placement);
+                let lines = lines - cleared as usize;
+                if lines == 0 {
+                    return Ok(Some(vec![step(board, held, placement)]));
+                }
+                let rows = rows(&after, lines);
+                if !fillable(&rows) {
+                    continue;
+                }
+                let key = (rows, next, hold);
+                if self.failed.contains(&key) {
+                    continue;
+                }
+                if let Some(mut steps) = self.run(&after, lines, next, hold)? {
+                    steps.push(step(board, held, placement));
+                    return Ok(Some(steps));
+                }
+                self.failed.insert(key);
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn step(board: &Grid, hold: bool, placement: Placement) -> Step {
+    Step {
+        hold,
+        placement,
+        board: *board,
+    }
+}
+
+/// The board with the piece locked in, in its color, and full rows cleared.
+fn place(board: &Grid, placement: &Placement) -> (Grid, u32) {
+    let mut board = *board;
+    for cell in &placement.cells {
+        board[cell.y as usize][cell.x as usize] = Block::new(1, Some(placement.shape.get_color()));
+    }
+    ai::lock(&board, 1, &[])
+}
+
+/// The bottom rows of the board as bitmasks, the bottom one last.
+fn rows(board: &Grid, lines: usize) -> Vec<u16> {
+    board[PLAYGROUND_HEIGHT as usize - lines..]
+        .iter()
+        .map(|row| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, block)| block.value != 0)
+                .fold(0, |mask, (x, _)| mask | 1 << x)
+        })
+        .collect()
+}
+
+/// Whether every enclosed group of empty cells could be filled with whole
+/// pieces, that is, has a multiple of four cells.
+fn fillable(rows: &[u16]) -> bool {
+    let width = PLAYGROUND_WIDTH as usize;
+    let mut seen = rows.to_vec();
+    for y in 0..rows.len() {
+        for x in 0..width {
+            if seen[y] & 1 << x != 0 {
+                continue;
+            }
+            let mut size: usize = 0;
+            let mut stack = vec![(y, x)];
+            seen[y] |= 1 << x;
+            while let Some((y, x)) = stack.pop() {
+                size += 1;
+                let neighbours = [
+                    (y.wrapping_sub(1), x),
+                    (y + 1, x),
+                    (y, x.wrapping_sub(1)),
+                    (y, x + 1),
+                ];
+                for (y, x) in neighbours {
+                    if y < rows.len() && x < width && seen[y] & 1 << x == 0 {
+                        seen[y] |= 1 << x;
+                        stack.push((y, x));
+                    }
+                }
+            }
+            if !size.is_multiple_of(4) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle::Puzzle;
+
+    fn parse(board: &str, queue: &str, hold: Option<Shape>) -> Problem {
+        let text = format!(
+            "name: Test\ngoal: perfect clear\nqueue: {}\nboard:\n{}",
+            queue, board
+        );
+        let puzzle = Puzzle::parse(&text).unwrap();
+        Problem {
+            board: puzzle.board,
+            piece: puzzle.queue[0],
+            hold,
+            queue: puzzle.queue[1..].to_vec(),
+        }
+    }
+
+    /// Plays the solution's inputs in a game and checks the board ends up empty.
+    fn check(problem: &Problem, solution: &Solution) {
+        let mut board = problem.board;
+        for step in &solution.steps {
+            assert_eq!(step.board, board);
+            board = place(&board, &step.placement).0;
+        }
+        assert_eq!(board, Game::create_grid());
+    }
+
+    #[test]
+    fn bundled_perfect_clear() {
+        let problem = parse("IIIIJJJL..\nZZTTTJLL..", "O", None);
+        let solution = solve(&problem, &Limits::default()).unwrap();
+        assert_eq!(solution.steps.len(), 1);
+        assert_eq!(
+            solution.steps[0].placement.cells,
+            vec![
+                Coord { y: 14, x: 8 },
+                Coord { y: 14, x: 9 },
+                Coord { y: 15, x: 8 },
+                Coord { y: 15, x: 9 }
+            ]
+        );
+        assert_eq!(solution.to_string(), "1. O\nIIIIJJJLoo\nZZTTTJLLoo\n\n");
+    }
+
+    #[test]
+    fn uses_hold() {
+        let problem = parse("IIIIJJJL..\nZZTTTJLL..", "T O", None);
+        let solution = solve(&problem, &Limits::default()).unwrap();
+        assert_eq!(solution.steps.len(), 1);
+        assert!(solution.steps[0].hold);
+        assert_eq!(solution.steps[0].inputs()[0], Action::Hold);
+
+        let held = parse("IIIIJJJL..\nZZTTTJLL..", "T", Some(Shape::O));
+        let solution = solve(&held, &Limits::default()).unwrap();
+        assert!(solution.steps[0].hold);
+        check(&held, &solution);
+    }
+
+    #[test]
+    fn four_line_wall() {
+        let problem = parse(
+            "LLOOZZ....\nJLOOSZ....\nJLLSSZ....\nJJSSTT....",
+            "I I I I",
+            None,
+        );
+        let solution = solve(&problem, &Limits::default()).unwrap();
+        assert_eq!(solution.steps.len(), 4);
+        check(&problem, &solution);
+    }
+
+    #[test]
+    fn opener_from_empty_board() {
+        let problem = parse("", "O O I I O", None);
+        let solution = solve(&problem, &Limits::default()).unwrap();
+        // Two lines take five pieces.
+        assert_eq!(solution.steps.len(), 5);
+        check(&problem, &solution);
+    }
+
+    #[test]
+    fn limits() {
+        let problem = parse("", "I O L J S Z T", None);
+        let shallow = Limits {
+            depth: 4,
+            ..Limits::default()
+        };
+        assert!(solve(&problem, &shallow).is_err());
+        let hasty = Limits {
+            timeout: Duration::ZERO,
+            ..Limits::default()
+        };
+        assert_eq!(solve(&problem, &hasty), Err("Timed out."));
+        let unsolvable = parse("IIIIJJJL..\nZZTTTJLL..", "S", None);
+        assert_eq!(
+            solve(&unsolvable, &Limits::default()),
+            Err("No perfect clear within the depth limit.")
+        );
+    }
+
+    #[test]
+    fn odd_regions_are_not_fillable() {
+        assert!(fillable(&[0b1111110000, 0b1111110000]));
+        assert!(!fillable(&[0b1111100000, 0b1111110000]));
+        assert!(!fillable(&[0b0000100000]));
+    }
+}
@@ -0,0 +1,314 @@
+use crate::core::{Block, Game, Grid, PLAYGROUND_HEIGHT, PLAYGROUND_WIDTH};
+use crate::shape::Shape;
+use crate::ui::Color;
+use std::fmt;
+use strum::IntoEnumIterator;
+
+/// Puzzles shipped with the game, in the order they show up in the puzzle-select screen.
+pub const BUNDLED: [&str; 5] = [
+    include_str!("../puzzles/tetris.txt"),
+    include_str!("../puzzles/double.txt"),
+    include_str!("../puzzles/perfect-clear.txt"),
+    include_str!("../puzzles/boxes.txt"),
+    include_str!("../puzzles/four-wall.txt"),
+];
+
+/// A fixed starting board and piece queue, written as:
+///
+/// ```text
+/// name: Tetris
+/// goal: lines 4
+/// queue: I
+/// board:
+/// LLLJJJSSO.
+/// LOOJTTTSS.
+/// ```
+///
+/// Board rows are bottom-aligned. `.` is an empty cell, a shape letter is a
+/// block in that shape's color and `#` is a garbage block.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Puzzle {
+    pub name: String,
+    pub goal: Goal,
+    pub queue: Vec<Shape>,
+    pub board: Grid,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Goal {
+    Lines(u32),
+    PerfectClear,
+}
+
+impl Goal {
+    pub fn is_met(&self, lines: u32, grid: &Grid) -> bool {
+        match self {
+            Goal::Lines(goal) => lines >= *goal,
+            Goal::PerfectClear => lines > 0 && *grid == Game::create_grid(),
+        }
+    }
+
+    fn parse(value: &str) -> Result<Goal, String> {
+        let words = value.split_whitespace().collect::<Vec<&str>>();
+        match words.as_slice() {
+            ["lines", count] => count
+                .parse()
+                .map(Goal::Lines)
+                .map_err(|_| format!("invalid line count: {}", count)),
+            ["perfect", "clear"] => Ok(Goal::PerfectClear),
+            _ => Err(format!("unknown goal: {}", value)),
+        }
+    }
+
+    /// The goal as written in puzzle files, the way `Goal::parse` reads it.
+    fn format(&self) -> String {
+        match self {
+            Goal::Lines(count) => format!("lines {}", count),
+            Goal::PerfectClear => "perfect clear".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Goal::Lines(1) => write!(f, "CLEAR 1 LINE"),
+            Goal::Lines(count) => write!(f, "CLEAR {} LINES", count),
+            Goal::PerfectClear => write!(f, "PERFECT CLEAR"),
+        }
+    }
+}
+
+/// Writes the puzzle back in the format `Puzzle::parse` reads.
+impl fmt::Display for Puzzle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "name: {}", self.name)?;
+        writeln!(f, "goal: {}", self.goal.format())?;
+        let queue = self.queue.iter().map(|shape| format!("{:?}", shape));
+        writeln!(f, "queue: {}", queue.collect::<Vec<String>>().join(" "))?;
+        writeln!(f, "board:")?;
+        let rows = self
+            .board
+            .iter()
+            .skip_while(|row| row.iter().all(|block| block.value == 0));
+        for row in rows {
+            writeln!(f, "{}", row.iter().map(format_cell).collect::<String>())?;
+        }
+        Ok(())
+    }
+}
+
+impl Puzzle {
+    pub fn load(path: &str) -> Result<Puzzle, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Puzzle::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn bundled() -> Vec<Puzzle> {
+        BUNDLED
+            .iter()
+            .map(|text| Puzzle::parse(text).unwrap())
+            .collect()
+    }
+
+    pub fn parse(text: &str) -> Result<Puzzle, String> {
+        let mut name = None;
+        let mut goal = None;
+        let mut queue = None;
+        let mut lines = text.lines();
+
+        for line in &mut lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or(format!("expected `key: value`, got: {}", line))?;
+            let value = value.trim();
+            match key {
+                "name" => name = Some(value.to_string()),
+                "goal" => goal = Some(Goal::parse(value)?),
+                "queue" => queue = Some(parse_queue(value)?),
+                "board" => break,
+                _ => return Err(format!("unknown key: {}", key)),
+            }
+        }
+
+        let rows = lines
+            .map(str::trim_end)
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<&str>>();
+
+        Ok(Puzzle {
+            name: name.ok_or("missing name")?,
+            goal: goal.ok_or("missing goal")?,
+            queue: queue.ok_or("missing queue")?,
+            board: parse_board(&rows)?,
+        })
+    }
+}
+
+/// Reads shape letters, spaces between them optional.
+pub fn parse_queue(value: &str) -> Result<Vec<Shape>, String> {
+    let queue = value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| {
+            c.to_string()
+                .parse()
+                .map_err(|_| format!("unknown shape: {}", c))
+        })
+        .collect::<Result<Vec<Shape>, String>>()?;
+    if queue.is_empty() {
+        return Err("empty queue".to_string());
+    }
+    Ok(queue)
+}
+
+/// The letter a block is written as in board diagrams.
+pub(crate) fn format_cell(block: &Block) -> char {
+    if block.value == 0 {
+        return '.';
+    }
+    Shape::iter()
+        .find(|shape| Some(shape.get_color()) == block.color)
+        .map_or('#', |shape| format!("{:?}", shape).chars().next().unwrap())
+}
+
+pub(crate) fn parse_board(rows: &[&str]) -> Result<Grid, String> {
+    if rows.len() > PLAYGROUND_HEIGHT as usize {
+        return Err(format!("board is taller than {} rows", PLAYGROUND_HEIGHT));
+    }
+
+    let mut grid = Game::create_grid();
+    let offset = PLAYGROUND_HEIGHT as usize - rows.len();
+    for (rowidx, row) in rows.iter().enumerate() {
+        if row.chars().count() != PLAYGROUND_WIDTH as usize {
+            return Err(format!(
+                "board row is not {} wide: {}",
+                PLAYGROUND_WIDTH, row
+            ));
+        }
+        for (colidx, cell) in row.chars().enumerate() {
+            grid[offset + rowidx][colidx] = match cell {
+                '.' => Block::new(0, None),
+                '#' => Block::new(1, Some(Color::Gray)),
+                _ => {
+                    let shape = cell
+                        .to_string()
+                        .parse::<Shape>()
+                        .map_err(|_| format!("unknown cell: {}", cell))?;
+                    Block::new(1, Some(shape.get_color()))
+                }
+            };
+        }
+    }
+    Ok(grid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn bundled() {
+        for text in BUNDLED.iter() {
+            assert!(Puzzle::parse(text).is_ok(), "{}", text);
+        }
+        assert_eq!(Puzzle::bundled().len(), BUNDLED.len());
+    }
+
+    #[test]
+    fn load() {
+        let puzzle = Puzzle::load("puzzles/tetris.txt").unwrap();
+        assert_eq!(puzzle.name, "Tetris");
+        assert_eq!(puzzle.goal, Goal::Lines(4));
+        assert_eq!(puzzle.queue, vec![Shape::I]);
+        let last_row = PLAYGROUND_HEIGHT as usize - 1;
+        assert_eq!(
+            puzzle.board[last_row][0],
+            Block::new(1, Some(Color::Yellow))
+        );
+        assert_eq!(puzzle.board[last_row][9], Block::new(0, None));
+        assert_eq!(puzzle.board[last_row - 4], Game::create_grid()[0]);
+    }
+
+    #[test]
+    fn load_missing_file() {
+        assert!(Puzzle::load("puzzles/missing.txt").is_err());
+    }
+
+    #[test]
+    fn parse() {
+        let puzzle =
+            Puzzle::parse("name: Test\ngoal: perfect clear\nqueue: T S\nboard:\n#........Z\n")
+                .unwrap();
+        assert_eq!(puzzle.goal, Goal::PerfectClear);
+        assert_eq!(puzzle.queue, vec![Shape::T, Shape::S]);
+        let last_row = PLAYGROUND_HEIGHT as usize - 1;
+        assert_eq!(puzzle.board[last_row][0], Block::new(1, Some(Color::Gray)));
+        assert_eq!(
+            puzzle.board[last_row][9],
+            Block::new(1, Some(Shape::Z.get_color()))
+        );
+    }
+
+    #[rstest(
+        text,
+        case("goal: lines 1\nqueue: I\nboard:\n"),
+        case("name: Test\nqueue: I\nboard:\n"),
+        case("name: Test\ngoal: lines 1\nboard:\n"),
+        case("name: Test\ngoal: lines\nqueue: I\nboard:\n"),
+        case("name: Test\ngoal: lines 1\nqueue: X\nboard:\n"),
+        case("name: Test\ngoal: lines 1\nqueue:\nboard:\n"),
+        case("name: Test\ngoal: lines 1\nqueue: I\nboard:\n..........?\n"),
+        case("name: Test\ngoal: lines 1\nqueue: I\nboard:\n....?.....\n"),
+        case("name: Test\nspeed: 3\n")
+    )]
+    fn parse_errors(text: &str) {
+        assert!(Puzzle::parse(text).is_err());
+    }
+
+    #[test]
+    fn parse_too_tall() {
+        let board = "..........\n".repeat(PLAYGROUND_HEIGHT as usize + 1);
+        let text = format!("name: Test\ngoal: lines 1\nqueue: I\nboard:\n{}", board);
+        assert!(Puzzle::parse(&text).is_err());
+    }
+
+    #[test]
+    fn to_string_round_trip() {
+        for puzzle in Puzzle::bundled() {
+            assert_eq!(Puzzle::parse(&puzzle.to_string()), Ok(puzzle));
+        }
+    }
+
+    #[rstest(
+        goal,
+        shown,
+        written,
+        case(Goal::Lines(1), "CLEAR 1 LINE", "lines 1"),
+        case(Goal::Lines(4), "CLEAR 4 LINES", "lines 4"),
+        case(Goal::PerfectClear, "PERFECT CLEAR", "perfect clear")
+    )]
+    fn goal_text(goal: Goal, shown: &str, written: &str) {
+        assert_eq!(goal.to_string(), shown);
+        assert_eq!(goal.format(), written);
+        assert_eq!(Goal::parse(written), Ok(goal));
+    }
+
+    #[test]
+    fn goal_is_met() {
+        let mut grid = Game::create_grid();
+        assert!(Goal::Lines(2).is_met(2, &grid));
+        assert!(!Goal::Lines(2).is_met(1, &grid));
+        assert!(Goal::PerfectClear.is_met(1, &grid));
+        assert!(!Goal::PerfectClear.is_met(0, &grid));
+        grid[0][0] = Block::new(1, None);
+        assert!(!Goal::PerfectClear.is_met(1, &grid));
+    }
+}
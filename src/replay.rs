@@ -0,0 +1,380 @@
+use crate::core::{Game, Input};
+use crate::mode::Settings;
+use crate::puzzle::Puzzle;
+use std::fmt;
+use std::str::FromStr;
+
+pub const VERSION: u32 = 1;
+const MAGIC: &str = "tetris-rs replay";
+
+/// Everything needed to play a game back: the seed, the settings and every
+/// input with the frame it happened on.
+///
+/// Replays are stored as text:
+///
+/// ```text
+/// tetris-rs replay v1
+/// seed: 1234
+/// mode: classic
+/// challenge: none
+/// big: false
+/// frames: 300
+/// inputs:
+/// 12 move-left
+/// 40 hard-drop
+/// ```
+///
+/// Puzzle games add a `puzzle:` line followed by the puzzle itself.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Replay {
+    pub seed: u64,
+    pub settings: Settings,
+    pub puzzle: Option<Puzzle>,
+    pub frames: u64,
+    pub inputs: Vec<Input>,
+}
+
+impl Replay {
+    pub fn record(game: &Game) -> Replay {
+        Replay {
+            seed: game.seed,
+            settings: game.settings,
+            puzzle: game.puzzle.clone(),
+            frames: game.frame,
+            inputs: game.inputs.clone(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Replay, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Replay::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_string()).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// A fresh game with the replay's seed, settings and puzzle.
+    pub fn new_game(&self) -> Game {
+        let mut game = Game::with_seed(self.settings, self.seed);
+        if let Some(puzzle) = &self.puzzle {
+            game.load_puzzle(puzzle.clone());
+        }
+        game
+    }
+
+    /// Applies the inputs recorded for the game's current frame and, unless the
+    /// replay is over, advances the game by one frame. Returns whether it advanced,
+    /// after which it is not to be called again with the same game.
+    pub fn step(&self, game: &mut Game) -> bool {
+        self.apply_inputs(game);
+        if game.frame >= self.frames || game.is_over() {
+            return false;
+        }
+        game.tick();
+        true
+    }
+
+    /// Plays the replay from the start until the given piece (counting from 1)
+    /// is the active one, or the replay ends. Also returns whether there is
+    /// more to play, as stepping past the end would apply the last inputs again.
+    pub fn seek(&self, piece: u32) -> (Game, bool) {
+        let mut game = self.new_game();
+        while game.pieces + 1 < piece {
+            if !self.step(&mut game) {
+                return (game, false);
+            }
+        }
+        (game, true)
+    }
+
+    /// The inputs applied in the `frames` frames up to and including `frame`.
+    pub fn recent_inputs(&self, frame: u64, frames: u64) -> &[Input] {
+        let start = self
+            .inputs
+            .partition_point(|input| input.frame + frames <= frame);
+        let end = self.inputs.partition_point(|input| input.frame <= frame);
+        &self.inputs[start..end]
+    }
+
+    fn apply_inputs(&self, game: &mut Game) {
+        let frame = game.frame;
+        let start = self.inputs.partition_point(|input| input.frame < frame);
+        let inputs = self.inputs[start..]
+            .iter()
+            .take_while(|input| input.frame == frame);
+        for input in inputs {
+            game.apply(input.action).ok();
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Replay, String> {
+        let mut lines = text.lines();
+
+        let version = lines
+            .next()
+            .and_then(|line| line.strip_prefix(MAGIC))
+            .and_then(|rest| rest.trim().strip_prefix('v'))
+            .ok_or("not a replay file")?;
+        if version != VERSION.to_string() {
+            return Err(format!(
+                "unsupported replay version {} (expected {})",
+                version, VERSION
+            ));
+        }
+
+        let mut seed = None;
+        let mut frames = None;
+        let mut settings = Settings::default();
+        for line in &mut lines {
+            let (key, value) = line
+                .split_once(':')
+                .ok_or(format!("expected `key: value`, got: {}", line))?;
+            let value = value.trim();
+            match key {
+                "seed" => seed = Some(parse_value(key, value)?),
+                "mode" => settings.mode = parse_value(key, value)?,
+                "challenge" => settings.challenge = parse_value(key, value)?,
+                "big" => settings.big = parse_value(key, value)?,
+                "frames" => frames = Some(parse_value(key, value)?),
+                "inputs" => break,
+                _ => return Err(format!("unknown key: {}", key)),
+            }
+        }
+
+        let mut inputs = vec![];
+        let mut puzzle = None;
+        while let Some(line) = lines.next() {
+            if line == "puzzle:" {
+                puzzle = Some(Puzzle::parse(&lines.collect::<Vec<&str>>().join("\n"))?);
+                break;
+            }
+            let (frame, action) = line
+                .split_once(' ')
+                .ok_or(format!("expected `frame action`, got: {}", line))?;
+            inputs.push(Input {
+                frame: parse_value("frame", frame)?,
+                action: parse_value("action", action)?,
+            });
+        }
+
+        if inputs.windows(2).any(|pair| pair[0].frame > pair[1].frame) {
+            return Err("inputs are out of order".to_string());
+        }
+
+        Ok(Replay {
+            seed: seed.ok_or("missing seed")?,
+            settings,
+            puzzle,
+            frames: frames.ok_or("missing frames")?,
+            inputs,
+        })
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} v{}", MAGIC, VERSION)?;
+        writeln!(f, "seed: {}", self.seed)?;
+        writeln!(f, "mode: {}", self.settings.mode)?;
+        writeln!(f, "challenge: {}", self.settings.challenge)?;
+        writeln!(f, "big: {}", self.settings.big)?;
+        writeln!(f, "frames: {}", self.frames)?;
+        writeln!(f, "inputs:")?;
+        for input in &self.inputs {
+            writeln!(f, "{} {}", input.frame, input.action)?;
+        }
+        if let Some(puzzle) = &self.puzzle {
+            writeln!(f, "puzzle:")?;
+            write!(f, "{}", puzzle)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_value<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {}: {}", key, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Action;
+    use crate::mode::{Challenge, Mode};
+    use rand::prelude::*;
+    use rstest::rstest;
+    use strum::IntoEnumIterator;
+
+    fn play(replay: &Replay) -> Game {
+        let mut game = replay.new_game();
+        while replay.step(&mut game) {}
+        game
+    }
+
+    /// Plays a game with random inputs, the way a person mashing keys would.
+    fn random_game(settings: Settings, seed: u64) -> Game {
+        let mut rng = StdRng::seed_from_u64(seed);
+        // Hold and 180 rotation are left out to keep the games these tests
+        // were written against.
+        let actions = Action::iter()
+            .filter(|action| !matches!(action, Action::Rotate180 | Action::Hold))
+            .collect::<Vec<Action>>();
+        let mut game = Game::with_seed(settings, seed);
+        while !game.is_over() && game.frame < 20_000 {
+            if rng.gen_ratio(1, 4) {
+                game.apply(*actions.choose(&mut rng).unwrap()).ok();
+            }
+            game.tick();
+        }
+        game
+    }
+
+    #[rstest(
+        settings,
+        case(Settings::default()),
+        case(Settings {
+            mode: Mode::Master,
+            challenge: Challenge::Fading,
+            big: false,
+        }),
+        case(Settings {
+            mode: Mode::Classic,
+            challenge: Challenge::None,
+            big: true,
+        })
+    )]
+    fn play_reproduces_game(settings: Settings) {
+        for seed in 0..5 {
+            let game = random_game(settings, seed);
+            let replay = Replay::parse(&Replay::record(&game).to_string()).unwrap();
+            let played = play(&replay);
+            assert_eq!(played.board(), game.board());
+            assert_eq!(played.score(), game.score());
+            assert_eq!(played.pieces, game.pieces);
+            assert_eq!(played.frame, game.frame);
+            assert_eq!(played.inputs, game.inputs);
+        }
+    }
+
+    #[test]
+    fn play_reproduces_puzzle() {
+        let mut game = Game::with_seed(Settings::default(), 3);
+        game.load_puzzle(Puzzle::load("puzzles/tetris.txt").unwrap());
+        for _ in 0..3 {
+            game.apply(Action::MoveRight).unwrap();
+        }
+        game.apply(Action::HardDrop).unwrap();
+        while !game.is_over() {
+            game.tick();
+        }
+
+        let replay = Replay::parse(&Replay::record(&game).to_string()).unwrap();
+        assert_eq!(replay.puzzle, game.puzzle);
+        let played = play(&replay);
+        assert!(played.solved);
+        assert_eq!(played.board(), game.board());
+    }
+
+    #[test]
+    fn seek() {
+        let replay = Replay::record(&random_game(Settings::default(), 2));
+        assert!(play(&replay).pieces > 5);
+
+        let (game, more) = replay.seek(5);
+        assert_eq!(game.pieces, 4);
+        assert!(more);
+        let mut played = replay.new_game();
+        while played.pieces < 4 {
+            replay.step(&mut played);
+        }
+        assert_eq!(game.board(), played.board());
+        assert_eq!(game.frame, played.frame);
+
+        assert_eq!(replay.seek(1).0.frame, 0);
+        let (end, more) = replay.seek(u32::MAX);
+        assert_eq!(end.frame, play(&replay).frame);
+        assert!(!more);
+    }
+
+    #[test]
+    fn recent_inputs() {
+        let input = |frame| Input {
+            frame,
+            action: Action::HardDrop,
+        };
+        let replay = Replay {
+            seed: 0,
+            settings: Settings::default(),
+            puzzle: None,
+            frames: 100,
+            inputs: vec![input(3), input(5), input(5), input(9)],
+        };
+        assert_eq!(replay.recent_inputs(5, 3), &[input(3), input(5), input(5)]);
+        assert_eq!(replay.recent_inputs(5, 2), &[input(5), input(5)]);
+        assert_eq!(replay.recent_inputs(8, 3), &[]);
+        assert_eq!(replay.recent_inputs(100, 100), replay.inputs.as_slice());
+    }
+
+    #[test]
+    fn round_trip() {
+        let replay = Replay {
+            seed: 42,
+            settings: Settings {
+                mode: Mode::Master,
+                challenge: Challenge::Invisible,
+                big: true,
+            },
+            puzzle: None,
+            frames: 100,
+            inputs: vec![
+                Input {
+                    frame: 3,
+                    action: Action::MoveLeft,
+                },
+                Input {
+                    frame: 3,
+                    action: Action::HardDrop,
+                },
+                Input {
+                    frame: 70,
+                    action: Action::Undo,
+                },
+            ],
+        };
+        assert_eq!(Replay::parse(&replay.to_string()), Ok(replay));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let replay = Replay::record(&random_game(Settings::default(), 1));
+        let path = std::env::temp_dir().join("tetris-rs-save-and-load.replay");
+        let path = path.to_str().unwrap();
+        replay.save(path).unwrap();
+        assert_eq!(Replay::load(path), Ok(replay));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[rstest(
+        text,
+        error,
+        case("", "not a replay file"),
+        case("hello\n", "not a replay file"),
+        case("tetris-rs replay v0\n", "unsupported replay version 0 (expected 1)"),
+        case("tetris-rs replay v1\nframes: 1\ninputs:\n", "missing seed"),
+        case("tetris-rs replay v1\nseed: x\n", "invalid seed: x"),
+        case(
+            "tetris-rs replay v1\nseed: 1\nframes: 1\ninputs:\n1 jump\n",
+            "invalid action: jump"
+        ),
+        case(
+            "tetris-rs replay v1\nseed: 1\nframes: 9\ninputs:\n5 undo\n2 redo\n",
+            "inputs are out of order"
+        )
+    )]
+    fn parse_errors(text: &str, error: &str) {
+        assert_eq!(Replay::parse(text), Err(error.to_string()));
+    }
+}
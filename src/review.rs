@@ -0,0 +1,64 @@
+use ncurses as nc;
+use tetris_rs::analysis::{Review, Step};
+use tetris_rs::ui;
+
+const KEY_N: i32 = b'n' as i32;
+const ESC: i32 = 27;
+
+/// Steps through the placements of a game, showing each next to the one the
+/// AI would have made.
+///
+/// - left, right: previous and next placement
+/// - `n`: jump to the next of the biggest mistakes, worst first
+/// - ESC: quit
+pub fn run(review: &Review, border_screen: nc::WINDOW, inner_screen: nc::WINDOW) {
+    if review.steps.is_empty() {
+        return;
+    }
+    nc::wtimeout(inner_screen, -1);
+
+    let mistakes = review
+        .mistakes(review.steps.len())
+        .iter()
+        .map(|step| step.piece as usize - 1)
+        .collect::<Vec<usize>>();
+    let mut next_mistake = 0;
+    let mut current = 0;
+    loop {
+        let step = &review.steps[current];
+        ui::erase_screens(inner_screen, border_screen);
+        nc::box_(border_screen, 0, 0);
+        ui::draw_landed_tetrominos(inner_screen, &step.board);
+        if let Some(best) = &step.best {
+            ui::draw_outline(inner_screen, &best.cells, best.shape.get_color());
+        }
+        ui::draw_cells(inner_screen, &step.player, step.shape.get_color());
+        ui::draw_review(&status(step, review.steps.len()));
+        ui::refresh_screens(inner_screen);
+
+        match nc::wgetch(inner_screen) {
+            ESC => return,
+            nc::KEY_LEFT => current = current.saturating_sub(1),
+            nc::KEY_RIGHT => current = (current + 1).min(review.steps.len() - 1),
+            KEY_N if !mistakes.is_empty() => {
+                current = mistakes[next_mistake];
+                next_mistake = (next_mistake + 1) % mistakes.len();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn status(step: &Step, placements: usize) -> Vec<String> {
+    let mut notes = format!("LOSS {:.2}", step.loss());
+    if step.holes_created > 0 {
+        notes += &format!("  HOLES +{}", step.holes_created);
+    }
+    if step.missed_t_spin {
+        notes += "  MISSED T-SPIN";
+    }
+    vec![
+        format!("REVIEW  PIECE {}/{}  [] AI", step.piece, placements),
+        notes,
+    ]
+}
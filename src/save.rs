@@ -0,0 +1,457 @@
+use crate::core::{Block, Coord, Game, Grid, Input, PLAYGROUND_HEIGHT};
+use crate::puzzle::Puzzle;
+use crate::random::Random;
+use crate::replay::Replay;
+use crate::shape::Shape;
+use crate::tetromino::Tetromino;
+use crate::ui::Color;
+use std::fmt::Write;
+use std::str::FromStr;
+use strum::IntoEnumIterator;
+
+pub const VERSION: u32 = 1;
+const MAGIC: &str = "tetris-rs save";
+
+/// Where an unfinished game is kept between runs.
+pub fn path() -> String {
+    match std::env::var("HOME") {
+        Ok(home) => format!("{}/.tetris-rs.save", home),
+        Err(_) => ".tetris-rs.save".to_string(),
+    }
+}
+
+pub fn save(game: &Game, path: &str) -> Result<(), String> {
+    std::fs::write(path, format(game)).map_err(|e| format!("{}: {}", path, e))
+}
+
+pub fn load(path: &str) -> Result<Game, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    parse(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Writes the whole state of a game, so it picks up exactly where it was left:
+///
+/// ```text
+/// tetris-rs save v1
+/// seed: 1234
+/// mode: classic
+/// ...
+/// tetromino: T 2 5 3
+/// next: S 0
+/// board:
+/// . . . . . . . . . .
+/// ...
+/// L120 L120 L120 J96 . . . . . .
+/// inputs:
+/// 12 move-left
+/// ```
+///
+/// The tetromino is its shape, rotation, row and column, and board cells are
+/// the shape whose color they have, or `#` for garbage, followed by the
+/// frame they landed on.
+/// Puzzle games add a `puzzle:` line followed by the puzzle itself.
+pub fn format(game: &Game) -> String {
+    let mut text = String::new();
+    let join = |values: &mut dyn Iterator<Item = String>| values.collect::<Vec<_>>().join(" ");
+    writeln!(text, "{} v{}", MAGIC, VERSION).unwrap();
+    writeln!(text, "seed: {}", game.seed).unwrap();
+    writeln!(text, "mode: {}", game.settings.mode).unwrap();
+    writeln!(text, "challenge: {}", game.settings.challenge).unwrap();
+    writeln!(text, "big: {}", game.settings.big).unwrap();
+    writeln!(text, "rng: {}", game.rng.state()).unwrap();
+    writeln!(text, "frame: {}", game.frame).unwrap();
+    writeln!(text, "score: {}", game.score()).unwrap();
+    writeln!(text, "lines: {}", game.lines).unwrap();
+    writeln!(text, "pieces: {}", game.pieces).unwrap();
+    writeln!(text, "level: {}", game.level).unwrap();
+    writeln!(text, "combo: {}", game.combo).unwrap();
+    writeln!(text, "entry-delay: {}", game.entry_delay).unwrap();
+    writeln!(text, "reveal-until: {}", game.reveal_until).unwrap();
+    let section_times = &mut game.section_times.iter().map(u64::to_string);
+    writeln!(text, "section-times: {}", join(section_times)).unwrap();
+    writeln!(text, "section-start: {}", game.section_start).unwrap();
+    writeln!(text, "gravity-progress: {}", game.gravity_progress).unwrap();
+    writeln!(text, "lock-progress: {}", game.lock_progress).unwrap();
+    writeln!(text, "used-undo: {}", game.used_undo).unwrap();
+    writeln!(text, "used-hint: {}", game.used_hint).unwrap();
+    if let Some(shape) = game.hold() {
+        writeln!(text, "hold: {:?}", shape).unwrap();
+    }
+    writeln!(text, "hold-used: {}", game.hold_used).unwrap();
+    let tetromino = game.piece();
+    writeln!(
+        text,
+        "tetromino: {:?} {} {} {}",
+        tetromino.shape,
+        rotation_index(tetromino),
+        tetromino.topleft.y,
+        tetromino.topleft.x
+    )
+    .unwrap();
+    let next = &game.next_tetromino;
+    writeln!(text, "next: {:?} {}", next.shape, rotation_index(next)).unwrap();
+    // The queue past the next piece, which only puzzles line up in advance.
+    let queue = &mut game
+        .queue()
+        .into_iter()
+        .skip(1)
+        .map(|shape| format!("{:?}", shape));
+    writeln!(text, "queue: {}", join(queue)).unwrap();
+    writeln!(text, "board:").unwrap();
+    for row in game.board().iter() {
+        writeln!(text, "{}", join(&mut row.iter().map(format_cell))).unwrap();
+    }
+    writeln!(text, "inputs:").unwrap();
+    for input in &game.inputs {
+        writeln!(text, "{} {}", input.frame, input.action).unwrap();
+    }
+    if let Some(puzzle) = &game.puzzle {
+        writeln!(text, "puzzle:").unwrap();
+        write!(text, "{}", puzzle).unwrap();
+    }
+    text
+}
+
+pub fn parse(text: &str) -> Result<Game, String> {
+    let mut lines = text.lines();
+
+    let version = lines
+        .next()
+        .and_then(|line| line.strip_prefix(MAGIC))
+        .and_then(|rest| rest.trim().strip_prefix('v'))
+        .ok_or("not a save file")?;
+    if version != VERSION.to_string() {
+        return Err(format!(
+            "unsupported save version {} (expected {})",
+            version, VERSION
+        ));
+    }
+
+    let mut game = Game::with_seed(Default::default(), 0);
+    let mut tetromino = None;
+    let mut next = None;
+    let mut hold = None;
+    let mut queue = vec![];
+    for line in &mut lines {
+        let (key, value) = line
+            .split_once(':')
+            .ok_or(format!("expected `key: value`, got: {}", line))?;
+        let value = value.trim();
+        match key {
+            "seed" => game.seed = parse_value(key, value)?,
+            "mode" => game.settings.mode = parse_value(key, value)?,
+            "challenge" => game.settings.challenge = parse_value(key, value)?,
+            "big" => game.settings.big = parse_value(key, value)?,
+            "rng" => game.rng = Random::new(parse_value(key, value)?),
+            "frame" => game.frame = parse_value(key, value)?,
+            "score" => game.score = parse_value(key, value)?,
+            "lines" => game.lines = parse_value(key, value)?,
+            "pieces" => game.pieces = parse_value(key, value)?,
+            "level" => game.level = parse_value(key, value)?,
+            "combo" => game.combo = parse_value(key, value)?,
+            "entry-delay" => game.entry_delay = parse_value(key, value)?,
+            "reveal-until" => game.reveal_until = parse_value(key, value)?,
+            "section-times" => {
+                game.section_times = value
+                    .split_whitespace()
+                    .map(|time| parse_value(key, time))
+                    .collect::<Result<_, _>>()?
+            }
+            "section-start" => game.section_start = parse_value(key, value)?,
+            "gravity-progress" => game.gravity_progress = parse_value(key, value)?,
+            "lock-progress" => game.lock_progress = parse_value(key, value)?,
+            "used-undo" => game.used_undo = parse_value(key, value)?,
+            "used-hint" => game.used_hint = parse_value(key, value)?,
+            "hold" => hold = Some(parse_value("shape", value)?),
+            "hold-used" => game.hold_used = parse_value(key, value)?,
+            "tetromino" => tetromino = Some(value.to_string()),
+            "next" => next = Some(value.to_string()),
+            "queue" => {
+                queue = value
+                    .split_whitespace()
+                    .map(|shape| parse_value("shape", shape))
+                    .collect::<Result<_, _>>()?
+            }
+            "board" => break,
+            _ => return Err(format!("unknown key: {}", key)),
+        }
+    }
+
+    let mut grid = Game::create_grid();
+    for row in grid.iter_mut() {
+        let line = lines.next().ok_or("board is cut short")?;
+        let cells = line
+            .split_whitespace()
+            .map(parse_cell)
+            .collect::<Result<Vec<Block>, String>>()?;
+        if cells.len() != row.len() {
+            return Err(format!("board row is not {} wide: {}", row.len(), line));
+        }
+        row.copy_from_slice(&cells);
+    }
+    if lines.next() != Some("inputs:") {
+        return Err(format!("board is taller than {} rows", PLAYGROUND_HEIGHT));
+    }
+
+    while let Some(line) = lines.next() {
+        if line == "puzzle:" {
+            game.puzzle = Some(Puzzle::parse(&lines.collect::<Vec<&str>>().join("\n"))?);
+            break;
+        }
+        let (frame, action) = line
+            .split_once(' ')
+            .ok_or(format!("expected `frame action`, got: {}", line))?;
+        game.inputs.push(Input {
+            frame: parse_value("frame", frame)?,
+            action: parse_value("action", action)?,
+        });
+    }
+
+    let scale = game.settings.scale();
+    let tetromino = parse_tetromino(grid, scale, &tetromino.ok_or("missing tetromino")?)?;
+    game.next_tetromino = parse_tetromino(grid, scale, &next.ok_or("missing next")?)?;
+    game.set_position(grid, tetromino, queue, hold);
+
+    // The undo history is not written out, playing the inputs back rebuilds it.
+    let replay = Replay::record(&game);
+    let mut replayed = replay.new_game();
+    while replay.step(&mut replayed) {}
+    game.take_history(replayed);
+    Ok(game)
+}
+
+fn rotation_index(tetromino: &Tetromino) -> usize {
+    tetromino
+        .shape
+        .get_possible_rotations()
+        .iter()
+        .position(|rotation| *rotation == tetromino.current_rotation)
+        .unwrap()
+}
+
+fn parse_tetromino(grid: Grid, scale: i32, value: &str) -> Result<Tetromino, String> {
+    let words = value.split_whitespace().collect::<Vec<&str>>();
+    let (shape, rotation, topleft) = match words.as_slice() {
+        [shape, rotation] => (shape, rotation, None),
+        [shape, rotation, y, x] => (shape, rotation, Some((y, x))),
+        _ => return Err(format!("invalid tetromino: {}", value)),
+    };
+    let shape = parse_value::<Shape>("shape", shape)?;
+    let rotation = shape
+        .get_possible_rotations()
+        .get(parse_value::<usize>("rotation", rotation)?)
+        .copied()
+        .ok_or(format!("invalid rotation: {}", value))?;
+    let mut tetromino = Tetromino::from_shape(grid, scale, shape, rotation);
+    if let Some((y, x)) = topleft {
+        tetromino.topleft = Coord {
+            y: parse_value("row", y)?,
+            x: parse_value("column", x)?,
+        };
+        tetromino
+            .check(tetromino.topleft, tetromino.current_rotation)
+            .map_err(|_| format!("tetromino does not fit: {}", value))?;
+    }
+    Ok(tetromino)
+}
+
+fn format_cell(block: &Block) -> String {
+    if block.value == 0 {
+        return ".".to_string();
+    }
+    let shape = Shape::iter()
+        .find(|shape| Some(shape.get_color()) == block.color)
+        .map_or("#".to_string(), |shape| format!("{:?}", shape));
+    format!("{}{}", shape, block.locked_at)
+}
+
+fn parse_cell(cell: &str) -> Result<Block, String> {
+    if cell == "." {
+        return Ok(Block::new(0, None));
+    }
+    let (shape, locked_at) = cell.split_at(cell.chars().next().map_or(0, char::len_utf8));
+    let color = match shape {
+        "#" => Color::Gray,
+        shape => parse_value::<Shape>("cell", shape)?.get_color(),
+    };
+    Ok(Block {
+        locked_at: parse_value("cell", locked_at)?,
+        ..Block::new(1, Some(color))
+    })
+}
+
+fn parse_value<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {}: {}", key, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Action, PLAYGROUND_WIDTH};
+    use crate::mode::{Challenge, Mode, Settings};
+    use rstest::rstest;
+
+    /// Plays a few pieces so that most of the state is not at its default.
+    fn played(settings: Settings) -> Game {
+        let mut game = Game::with_seed(settings, 7);
+        let actions = [
+            Action::MoveLeft,
+            Action::HardDrop,
+            Action::RotateRight,
+            Action::Hold,
+            Action::MoveRight,
+            Action::MoveRight,
+            Action::HardDrop,
+        ];
+        for action in actions.iter().cycle().take(12) {
+            game.apply(*action).ok();
+            for _ in 0..20 {
+                game.tick();
+            }
+        }
+        game.apply(Action::MoveRight).ok();
+        game
+    }
+
+    fn assert_same(a: &Game, b: &Game) {
+        assert_eq!(a.board(), b.board());
+        assert_eq!(a.piece().shape, b.piece().shape);
+        assert_eq!(a.piece().topleft, b.piece().topleft);
+        assert_eq!(a.piece().current_rotation, b.piece().current_rotation);
+        assert_eq!(a.queue(), b.queue());
+        assert_eq!(a.hold(), b.hold());
+        assert_eq!(a.queue(), b.queue());
+        assert_eq!(a.settings, b.settings);
+        assert_eq!(a.score(), b.score());
+        assert_eq!(a.pieces, b.pieces);
+        assert_eq!(a.frame, b.frame);
+        assert_eq!(a.inputs, b.inputs);
+        assert_eq!(a.puzzle, b.puzzle);
+        assert_eq!(a.used_hint, b.used_hint);
+    }
+
+    #[rstest(
+        settings,
+        case(Settings::default()),
+        case(Settings {
+            mode: Mode::Master,
+            challenge: Challenge::Fading,
+            big: false,
+        }),
+        case(Settings {
+            mode: Mode::Classic,
+            challenge: Challenge::None,
+            big: true,
+        })
+    )]
+    fn round_trip(settings: Settings) {
+        let game = played(settings);
+        assert!(game.pieces > 0);
+        let loaded = parse(&format(&game)).unwrap();
+        assert_same(&loaded, &game);
+        assert_eq!(format(&loaded), format(&game));
+    }
+
+    #[test]
+    fn resumed_game_plays_on_the_same() {
+        let mut game = played(Settings::default());
+        let mut resumed = parse(&format(&game)).unwrap();
+        for _ in 0..120 {
+            game.tick();
+            resumed.tick();
+        }
+        assert_same(&resumed, &game);
+        assert!(!game.is_over());
+
+        game.apply(Action::Undo).unwrap();
+        resumed.apply(Action::Undo).unwrap();
+        assert_same(&resumed, &game);
+
+        let replay = Replay::record(&resumed);
+        let mut played = replay.new_game();
+        while replay.step(&mut played) {}
+        assert_same(&played, &game);
+    }
+
+    #[test]
+    fn round_trip_used_hint() {
+        let mut game = played(Settings::default());
+        game.used_hint = true;
+        let loaded = parse(&format(&game)).unwrap();
+        assert!(loaded.used_hint);
+        assert!(!loaded.is_high_score_eligible());
+    }
+
+    #[test]
+    fn round_trip_puzzle() {
+        let mut game = Game::with_seed(Settings::default(), 1);
+        game.load_puzzle(Puzzle::load("puzzles/boxes.txt").unwrap());
+        game.apply(Action::HardDrop).unwrap();
+        game.tick();
+        assert_same(&parse(&format(&game)).unwrap(), &game);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let game = played(Settings::default());
+        let path = std::env::temp_dir().join("tetris-rs-save-and-load.save");
+        let path = path.to_str().unwrap();
+        save(&game, path).unwrap();
+        assert_same(&load(path).unwrap(), &game);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[rstest(
+        text,
+        error,
+        case("", "not a save file"),
+        case("tetris-rs replay v1\n", "not a save file"),
+        case(
+            "tetris-rs save v0\nseed: 1\n",
+            "unsupported save version 0 (expected 1)"
+        ),
+        case("tetris-rs save v1\nscore: lots\n", "invalid score: lots"),
+        case("tetris-rs save v1\nboard:\n", "board is cut short"),
+        case("tetris-rs save v1\nspeed: 3\n", "unknown key: speed")
+    )]
+    fn parse_errors(text: &str, error: &str) {
+        assert_eq!(parse(text).err(), Some(error.to_string()));
+    }
+
+    #[test]
+    fn parse_bad_board() {
+        let game = played(Settings::default());
+        let text = format(&game).replace("board:\n", "board:\n. X12 . . . . . . . .\n");
+        assert_eq!(parse(&text).err(), Some("invalid cell: X".to_string()));
+        let text = format(&game).replace("tetromino: ", "tetromino: Q ");
+        assert!(parse(&text).is_err());
+    }
+
+    #[test]
+    fn parse_misplaced_tetromino() {
+        let game = played(Settings::default());
+        let line = format(&game)
+            .lines()
+            .find(|line| line.starts_with("tetromino: "))
+            .unwrap()
+            .to_string();
+        let text = format(&game).replace(&line, "tetromino: L 3 40 40");
+        assert_eq!(
+            parse(&text).err(),
+            Some("tetromino does not fit: L 3 40 40".to_string())
+        );
+        // An O whose top left block sits on one of the stack's.
+        let (y, x) = (0..PLAYGROUND_HEIGHT as usize)
+            .flat_map(|y| (0..PLAYGROUND_WIDTH as usize).map(move |x| (y, x)))
+            .find(|&(y, x)| game.board()[y][x].value != 0)
+            .unwrap();
+        let sunk = format!("tetromino: O 0 {} {}", y as i32 - 2, x as i32 - 2);
+        let text = format(&game).replace(&line, &sunk);
+        assert_eq!(
+            parse(&text).err(),
+            Some(sunk.replace("tetromino: ", "tetromino does not fit: "))
+        );
+    }
+}
@@ -1,11 +1,14 @@
 use crate::ui::Color;
 
+use strum_macros::{EnumIter, EnumString};
+
 use rand::{
     distributions::{Distribution, Standard},
     Rng,
 };
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Copy, Debug, EnumIter, EnumString, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Shape {
     O,
     I,
@@ -41,7 +44,18 @@ impl Shape {
         }
     }
 
-    pub fn to_4x4(&self, rotation: Rotation) -> ShapeVec {
+    /// The shape seen in a mirror: S and Z swap, as do J and L.
+    pub fn mirrored(self) -> Shape {
+        match self {
+            Shape::S => Shape::Z,
+            Shape::Z => Shape::S,
+            Shape::J => Shape::L,
+            Shape::L => Shape::J,
+            shape => shape,
+        }
+    }
+
+    pub fn to_4x4(self, rotation: Rotation) -> ShapeVec {
         (0..16)
             .map(|i| (rotation >> (15 - i)) & 1)
             .collect::<Vec<Rotation>>()
@@ -239,4 +253,19 @@ mod tests {
             assert_eq!(shape.to_4x4(possible_rotation), *exp);
         }
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        use strum::IntoEnumIterator;
+
+        for shape in Shape::iter() {
+            let json = serde_json::to_string(&shape).unwrap();
+            assert_eq!(serde_json::from_str::<Shape>(&json).unwrap(), shape);
+        }
+        for color in Color::iter() {
+            let json = serde_json::to_string(&color).unwrap();
+            assert_eq!(serde_json::from_str::<Color>(&json).unwrap(), color);
+        }
+    }
 }
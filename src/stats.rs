@@ -0,0 +1,318 @@
+use crate::core::{Game, FRAMES_PER_SECOND};
+use crate::event::{ClearType, Event};
+use crate::shape::Shape;
+use std::collections::HashMap;
+use std::fmt;
+
+/// The order shapes are listed in, as on the NES.
+pub const SHAPES: [Shape; 7] = [
+    Shape::T,
+    Shape::J,
+    Shape::Z,
+    Shape::O,
+    Shape::S,
+    Shape::L,
+    Shape::I,
+];
+
+/// Running statistics of a game. Feed it the game's events, and keep its
+/// time with `keep_time`. Placements taken back with undo stay counted, as
+/// the keys were still pressed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    /// The frame counting started on.
+    started: u64,
+    /// Frames played since.
+    pub frames: u64,
+    pub pieces: u32,
+    /// Moves, rotations and holds, with one drop for every piece.
+    pub keys: u32,
+    pub lines: u32,
+    /// Lines of garbage the clears would have sent.
+    pub attack: u32,
+    pub singles: u32,
+    pub doubles: u32,
+    pub triples: u32,
+    pub tetrises: u32,
+    /// Line-clearing T-spins, which do not count as singles, doubles or
+    /// triples.
+    pub t_spins: u32,
+    /// How many of each shape came out of the randomizer. Pieces coming back
+    /// from hold are not counted again.
+    pub spawned: HashMap<Shape, u32>,
+    /// Pieces since the last I piece.
+    pub drought: u32,
+    pub longest_drought: u32,
+    /// The held piece, to tell pieces coming back from hold from new ones.
+    held: Option<Shape>,
+    /// Whether the next piece to spawn comes back from hold.
+    unholding: bool,
+}
+
+impl Stats {
+    /// Starts counting from where the game is, so resumed games are timed
+    /// from when they were resumed.
+    pub fn new(game: &Game) -> Stats {
+        let mut stats = Stats {
+            started: game.frame,
+            held: game.hold(),
+            ..Stats::default()
+        };
+        stats.count(game.piece().shape);
+        stats
+    }
+
+    pub fn observe(&mut self, event: &Event) {
+        match event {
+            Event::Locked { presses, .. } => {
+                self.pieces += 1;
+                self.keys += presses + 1;
+            }
+            Event::Spawned { shape, .. } => {
+                if !self.unholding {
+                    self.count(*shape);
+                }
+                self.unholding = false;
+            }
+            Event::HoldUsed { held, presses } => {
+                self.keys += presses + 1;
+                self.unholding = self.held.replace(*held).is_some();
+            }
+            Event::LinesCleared { clear, .. } => {
+                self.lines += clear.lines();
+                self.attack += clear.attack();
+                let count = match clear {
+                    ClearType::Single => &mut self.singles,
+                    ClearType::Double => &mut self.doubles,
+                    ClearType::Triple => &mut self.triples,
+                    ClearType::Tetris => &mut self.tetrises,
+                    _ => &mut self.t_spins,
+                };
+                *count += 1;
+            }
+            _ => {}
+        }
+    }
+
+    fn count(&mut self, shape: Shape) {
+        *self.spawned.entry(shape).or_insert(0) += 1;
+        if shape == Shape::I {
+            self.drought = 0;
+        } else {
+            self.drought += 1;
+            self.longest_drought = self.longest_drought.max(self.drought);
+        }
+    }
+
+    pub fn keep_time(&mut self, game: &Game) {
+        self.frames = game.frame.saturating_sub(self.started);
+    }
+
+    pub fn seconds(&self) -> f64 {
+        self.frames as f64 / FRAMES_PER_SECOND as f64
+    }
+
+    /// Pieces per second.
+    pub fn pps(&self) -> f64 {
+        per(self.pieces as f64, self.seconds())
+    }
+
+    /// Keys per piece.
+    pub fn kpp(&self) -> f64 {
+        per(self.keys as f64, self.pieces as f64)
+    }
+
+    /// Attack per minute.
+    pub fn apm(&self) -> f64 {
+        per(self.attack as f64, self.seconds() / 60.0)
+    }
+}
+
+/// Zero until there is something to divide by.
+fn per(amount: f64, by: f64) -> f64 {
+    if by > 0.0 {
+        amount / by
+    } else {
+        0.0
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "time: {:.2}s", self.seconds())?;
+        writeln!(f, "pieces: {}", self.pieces)?;
+        writeln!(f, "pps: {:.2}", self.pps())?;
+        writeln!(f, "kpp: {:.2}", self.kpp())?;
+        writeln!(f, "lines: {}", self.lines)?;
+        writeln!(f, "apm: {:.2}", self.apm())?;
+        writeln!(
+            f,
+            "clears: {} singles, {} doubles, {} triples, {} tetrises, {} t-spins",
+            self.singles, self.doubles, self.triples, self.tetrises, self.t_spins
+        )?;
+        let spawned = SHAPES
+            .iter()
+            .map(|shape| format!("{:?} {}", shape, self.spawned.get(shape).unwrap_or(&0)))
+            .collect::<Vec<String>>();
+        writeln!(
+            f,
+            "spawned: {} (I drought {}, longest {})",
+            spawned.join(", "),
+            self.drought,
+            self.longest_drought
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Coord;
+    use crate::mode::Settings;
+    use crate::shape::Shape;
+
+    fn locked(presses: u32) -> Event {
+        Event::Locked {
+            shape: Shape::T,
+            cells: vec![Coord { y: 15, x: 0 }],
+            presses,
+        }
+    }
+
+    fn cleared(clear: ClearType) -> Event {
+        Event::LinesCleared {
+            rows: vec![],
+            clear,
+        }
+    }
+
+    #[test]
+    fn counts_events() {
+        let mut game = Game::with_seed(Settings::default(), 1);
+        game.frame = 600;
+        let mut stats = Stats::new(&game);
+        for event in [
+            locked(2),
+            Event::HoldUsed {
+                held: Shape::I,
+                presses: 0,
+            },
+            locked(0),
+            cleared(ClearType::Tetris),
+            locked(3),
+            cleared(ClearType::TSpinDouble),
+            locked(1),
+            cleared(ClearType::Single),
+        ] {
+            stats.observe(&event);
+        }
+        game.frame = 600 + 2 * FRAMES_PER_SECOND as u64;
+        stats.keep_time(&game);
+
+        assert_eq!(stats.pieces, 4);
+        assert_eq!(stats.keys, 11);
+        assert_eq!(stats.lines, 7);
+        assert_eq!(stats.attack, 8);
+        assert_eq!(
+            (stats.singles, stats.doubles, stats.tetrises, stats.t_spins),
+            (1, 0, 1, 1)
+        );
+        assert_eq!(stats.seconds(), 2.0);
+        assert_eq!(stats.pps(), 2.0);
+        assert_eq!(stats.kpp(), 2.75);
+        assert_eq!(stats.apm(), 240.0);
+        assert!(stats
+            .to_string()
+            .contains("clears: 1 singles, 0 doubles, 0 triples, 1 tetrises, 1 t-spins\n"));
+    }
+
+    #[test]
+    fn follows_a_game() {
+        use crate::ai::{self, Bot};
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut game = Game::with_seed(Settings::default(), 1);
+        let stats = Rc::new(RefCell::new(Stats::new(&game)));
+        let sink = stats.clone();
+        game.subscribe(move |event| sink.borrow_mut().observe(event));
+        ai::play(&mut game, &mut Bot::new(1, None), 30).unwrap();
+        let mut stats = stats.borrow_mut();
+        stats.keep_time(&game);
+        assert_eq!(stats.pieces, game.pieces);
+        assert_eq!(stats.lines, game.lines);
+        assert_eq!(stats.frames, game.frame);
+        // Every piece placed, the active one and the held one came out once.
+        assert_eq!(
+            stats.spawned.values().sum::<u32>(),
+            game.pieces + 1 + game.hold().is_some() as u32
+        );
+        assert!(stats.pps() > 0.0);
+    }
+
+    #[test]
+    fn counts_keys_before_hold() {
+        use crate::core::Action;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut game = Game::with_seed(Settings::default(), 1);
+        let stats = Rc::new(RefCell::new(Stats::new(&game)));
+        let sink = stats.clone();
+        game.subscribe(move |event| sink.borrow_mut().observe(event));
+        for action in [
+            Action::MoveLeft,
+            Action::RotateRight,
+            Action::Hold,
+            Action::MoveRight,
+            Action::HardDrop,
+        ] {
+            game.apply(action).unwrap();
+        }
+        while game.pieces == 0 {
+            game.tick();
+        }
+        // Two presses before the hold, the hold, one after and the drop.
+        assert_eq!(stats.borrow().keys, 5);
+        assert_eq!(stats.borrow().kpp(), 5.0);
+    }
+
+    #[test]
+    fn counts_spawned_shapes() {
+        let game = Game::with_seed(Settings::default(), 1);
+        let first = game.piece().shape;
+        let mut stats = Stats::new(&game);
+        let spawned = |shape| Event::Spawned { shape, rotation: 0 };
+        for event in [
+            spawned(Shape::T),
+            // Holding the T brings out a new S, holding the S brings back the T.
+            Event::HoldUsed {
+                held: Shape::T,
+                presses: 0,
+            },
+            spawned(Shape::S),
+            Event::HoldUsed {
+                held: Shape::S,
+                presses: 0,
+            },
+            spawned(Shape::T),
+            spawned(Shape::I),
+            spawned(Shape::Z),
+        ] {
+            stats.observe(&event);
+        }
+        let mut expected = HashMap::new();
+        for shape in [first, Shape::T, Shape::S, Shape::I, Shape::Z] {
+            *expected.entry(shape).or_insert(0) += 1;
+        }
+        assert_eq!(stats.spawned, expected);
+        assert_eq!(stats.drought, 1);
+        assert!(stats.longest_drought >= 2);
+    }
+
+    #[test]
+    fn nothing_to_divide_by() {
+        let stats = Stats::default();
+        assert_eq!((stats.pps(), stats.kpp(), stats.apm()), (0.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,732 @@
+//! The [Tetris Bot Protocol](https://github.com/tetris-bot-protocol/tbp-spec):
+//! JSON messages, one per line, between a frontend and a bot over the bot's
+//! stdin and stdout. `Client` lets an external bot play a `Game`, and `serve`
+//! answers a frontend on behalf of a bot.
+
+use crate::ai::{Bot, Player};
+use crate::core::{Action, Block, Coord, Game, Grid, State, PLAYGROUND_HEIGHT, PLAYGROUND_WIDTH};
+use crate::movegen::placements;
+use crate::shape::Shape;
+use crate::tetromino::Tetromino;
+use crate::ui::Color;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// Rows in a TBP board. Ours only has the bottom `PLAYGROUND_HEIGHT`.
+const BOARD_HEIGHT: usize = 40;
+
+/// How long a bot gets to exit once told to quit before it is killed.
+const QUIT_GRACE: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, Display, EnumIter, EnumString, PartialEq)]
+#[strum(serialize_all = "lowercase")]
+pub enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+/// Where a piece goes, as TBP describes it: the SRS orientation and the
+/// position of the piece's center, counted from the bottom left corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Location {
+    pub shape: Shape,
+    pub orientation: Orientation,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Location {
+    /// The grid cells the piece covers, sorted like `Placement::cells`.
+    pub fn cells(&self) -> Vec<Coord> {
+        let mut cells = offsets(self.shape, self.orientation)
+            .iter()
+            .map(|(dx, dy)| Coord {
+                y: PLAYGROUND_HEIGHT - 1 - (self.y + dy),
+                x: self.x + dx,
+            })
+            .collect::<Vec<Coord>>();
+        cells.sort_by_key(|cell| (cell.y, cell.x));
+        cells
+    }
+
+    /// The location covering exactly the given cells, if there is one.
+    pub fn from_cells(shape: Shape, cells: &[Coord]) -> Option<Location> {
+        let mut cells = cells.to_vec();
+        cells.sort_by_key(|cell| (cell.y, cell.x));
+        let first = cells.first()?;
+        Orientation::iter().find_map(|orientation| {
+            // The first cell is the top left one, which is also the first
+            // of the sorted offsets.
+            let mut location = Location {
+                shape,
+                orientation,
+                x: 0,
+                y: 0,
+            };
+            let origin = location.cells()[0];
+            location.x = first.x - origin.x;
+            location.y = origin.y - first.y;
+            Some(location).filter(|location| location.cells() == cells)
+        })
+    }
+
+    fn to_json(self) -> Value {
+        json!({
+            "type": format!("{:?}", self.shape),
+            "orientation": self.orientation.to_string(),
+            "x": self.x,
+            "y": self.y,
+        })
+    }
+
+    fn from_json(value: &Value) -> Result<Location, String> {
+        Ok(Location {
+            shape: parse_shape(&value["type"])?,
+            orientation: value["orientation"]
+                .as_str()
+                .and_then(|orientation| orientation.parse().ok())
+                .ok_or(format!("invalid orientation: {}", value["orientation"]))?,
+            x: parse_coordinate(&value["x"])?,
+            y: parse_coordinate(&value["y"])?,
+        })
+    }
+}
+
+/// The cells of a piece facing north relative to its center, with y up,
+/// turned clockwise for the other orientations.
+fn offsets(shape: Shape, orientation: Orientation) -> [(i32, i32); 4] {
+    let mut offsets = match shape {
+        Shape::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+        Shape::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        Shape::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+        Shape::L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+        Shape::J => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+        Shape::S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+        Shape::Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+    };
+    for _ in 0..orientation as usize {
+        for offset in offsets.iter_mut() {
+            *offset = (offset.1, -offset.0);
+        }
+    }
+    offsets
+}
+
+/// What a bot knows about the game: the board, the pieces it can see, the
+/// first of which is the one to place, and the held piece.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    pub board: Grid,
+    pub queue: VecDeque<Shape>,
+    pub hold: Option<Shape>,
+}
+
+impl Position {
+    pub fn from_game(game: &Game) -> Position {
+        let mut queue = VecDeque::from(game.queue());
+        queue.push_front(game.piece().shape);
+        Position {
+            board: *game.board(),
+            queue,
+            hold: game.hold(),
+        }
+    }
+
+    /// Locks a piece at the location, holding the first piece in the queue
+    /// first if the location is for another one.
+    pub fn play(&mut self, location: &Location) -> Result<(), String> {
+        let current = self.queue.pop_front().ok_or("no piece to play")?;
+        if location.shape != current {
+            let swapped = match self.hold {
+                Some(held) => held,
+                None => self.queue.pop_front().ok_or("no piece to hold")?,
+            };
+            if swapped != location.shape {
+                return Err(format!("cannot play a {:?}", location.shape));
+            }
+            self.hold = Some(current);
+        }
+
+        for Coord { y, x } in location.cells() {
+            if !(0..PLAYGROUND_HEIGHT).contains(&y) || !(0..PLAYGROUND_WIDTH).contains(&x) {
+                return Err("location is out of bounds".to_string());
+            }
+            self.board[y as usize][x as usize] = Block::new(1, Some(location.shape.get_color()));
+        }
+        for y in 0..self.board.len() {
+            if self.board[y].iter().all(|block| block.value != 0) {
+                self.board[..y + 1].rotate_right(1);
+                self.board[0] = [Block::new(0, None); PLAYGROUND_WIDTH as usize];
+            }
+        }
+        Ok(())
+    }
+
+    fn start_message(&self) -> Value {
+        let mut board = vec![vec![Value::Null; PLAYGROUND_WIDTH as usize]; BOARD_HEIGHT];
+        for (row, cells) in self.board.iter().rev().zip(board.iter_mut()) {
+            for (block, cell) in row.iter().zip(cells.iter_mut()) {
+                if block.value != 0 {
+                    let shape = Shape::iter().find(|shape| Some(shape.get_color()) == block.color);
+                    *cell = json!(shape.map_or("G".to_string(), |shape| format!("{:?}", shape)));
+                }
+            }
+        }
+        json!({
+            "type": "start",
+            "hold": self.hold.map(|shape| format!("{:?}", shape)),
+            "queue": self.queue.iter().map(|shape| format!("{:?}", shape)).collect::<Vec<String>>(),
+            "combo": 0,
+            "back_to_back": false,
+            "board": board,
+        })
+    }
+
+    fn from_start(message: &Value) -> Result<Position, String> {
+        let mut board = Game::create_grid();
+        let rows = message["board"].as_array().ok_or("missing board")?;
+        for (y, row) in rows.iter().enumerate() {
+            let row = row.as_array().ok_or("invalid board")?;
+            for (x, cell) in row.iter().enumerate().filter(|(_, cell)| !cell.is_null()) {
+                if y >= PLAYGROUND_HEIGHT as usize || x >= PLAYGROUND_WIDTH as usize {
+                    return Err("board does not fit".to_string());
+                }
+                // Garbage ("G") has no shape.
+                let color = parse_shape(cell).map_or(Color::Gray, |shape| shape.get_color());
+                board[PLAYGROUND_HEIGHT as usize - 1 - y][x] = Block::new(1, Some(color));
+            }
+        }
+        let queue = message["queue"]
+            .as_array()
+            .ok_or("missing queue")?
+            .iter()
+            .map(parse_shape)
+            .collect::<Result<VecDeque<Shape>, String>>()?;
+        let hold = match &message["hold"] {
+            Value::Null => None,
+            hold => Some(parse_shape(hold)?),
+        };
+        Ok(Position { board, queue, hold })
+    }
+}
+
+fn parse_shape(value: &Value) -> Result<Shape, String> {
+    value
+        .as_str()
+        .and_then(|shape| shape.parse().ok())
+        .ok_or(format!("invalid piece: {}", value))
+}
+
+fn parse_coordinate(value: &Value) -> Result<i32, String> {
+    value
+        .as_i64()
+        .map(|coordinate| coordinate as i32)
+        .ok_or(format!("invalid coordinate: {}", value))
+}
+
+fn send(output: &mut dyn Write, message: &Value) -> Result<(), String> {
+    writeln!(output, "{}", message)
+        .and_then(|_| output.flush())
+        .map_err(|e| format!("cannot reach the bot: {}", e))
+}
+
+/// The next message, or `None` once the connection is closed.
+fn receive(input: &mut dyn BufRead) -> Result<Option<Value>, String> {
+    let mut line = String::new();
+    match input.read_line(&mut line) {
+        Ok(0) => Ok(None),
+        Ok(_) => serde_json::from_str(&line)
+            .map(Some)
+            .map_err(|e| format!("invalid message: {}", e)),
+        Err(e) => Err(format!("cannot read the message: {}", e)),
+    }
+}
+
+/// An external bot playing a game through TBP.
+pub struct Client {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    child: Option<Child>,
+    /// The name the bot introduced itself with.
+    pub name: String,
+    /// The piece count the bot's position was last in sync with the game at.
+    synced_at: Option<u32>,
+    /// How many of the pieces in the game's queue the bot was told about.
+    known: usize,
+    /// Whether a piece was dropped without the bot's move, leaving its
+    /// position behind the game's.
+    stale: bool,
+}
+
+impl Client {
+    /// Starts the bot with the given command line.
+    pub fn spawn(command: &str) -> Result<Client, String> {
+        let mut args = command.split_whitespace();
+        let program = args.next().ok_or("the bot command is empty")?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("{}: {}", program, e))?;
+        let input = BufReader::new(child.stdout.take().unwrap());
+        let output = child.stdin.take().unwrap();
+        let mut client = Client::connect(input, output)?;
+        client.child = Some(child);
+        Ok(client)
+    }
+
+    /// Talks to a bot over the given streams: waits for it to introduce
+    /// itself and to accept the rules.
+    pub fn connect(
+        input: impl BufRead + 'static,
+        output: impl Write + 'static,
+    ) -> Result<Client, String> {
+        let mut client = Client {
+            input: Box::new(input),
+            output: Box::new(output),
+            child: None,
+            name: String::new(),
+            synced_at: None,
+            known: 0,
+            stale: false,
+        };
+        let info = client.expect("info")?;
+        client.name = info["name"].as_str().unwrap_or_default().to_string();
+        client.send(&json!({"type": "rules"}))?;
+        client.expect("ready")?;
+        Ok(client)
+    }
+
+    fn send(&mut self, message: &Value) -> Result<(), String> {
+        send(&mut self.output, message)
+    }
+
+    /// Waits for a message of the given type, skipping the others.
+    fn expect(&mut self, kind: &str) -> Result<Value, String> {
+        loop {
+            let message = receive(&mut self.input)?.ok_or("the connection was closed")?;
+            match message["type"].as_str() {
+                Some(received) if received == kind => return Ok(message),
+                Some("error") => return Err(format!("the bot refused: {}", message["reason"])),
+                _ => {}
+            }
+        }
+    }
+
+    /// Brings the bot's position up to date: new pieces in the queue if it
+    /// only missed those, a fresh start after undos or on the first piece.
+    fn sync(&mut self, game: &Game) -> Result<(), String> {
+        let position = Position::from_game(game);
+        let visible = position.queue.len();
+        if self.synced_at == Some(game.pieces) && !self.stale && self.known <= visible {
+            for &shape in position.queue.range(self.known..) {
+                self.send(&json!({"type": "new_piece", "piece": format!("{:?}", shape)}))?;
+            }
+        } else {
+            if self.synced_at.is_some() {
+                self.send(&json!({"type": "stop"}))?;
+            }
+            self.send(&position.start_message())?;
+            self.stale = false;
+        }
+        self.synced_at = Some(game.pieces);
+        self.known = visible;
+        Ok(())
+    }
+}
+
+impl Player for Client {
+    /// Asks the bot for a move for every new piece and makes the first
+    /// suggested move that is reachable, or hard drops if none is.
+    fn step(&mut self, game: &mut Game) -> Result<(), String> {
+        if game.state() != State::Playing || self.synced_at == Some(game.pieces + 1) {
+            return Ok(());
+        }
+        if game.settings.scale() != 1 {
+            return Err("TBP bots cannot play big games".to_string());
+        }
+        self.sync(game)?;
+        self.send(&json!({"type": "suggest"}))?;
+        let suggestion = self.expect("suggestion")?;
+        let moves = suggestion["moves"].as_array().cloned().unwrap_or_default();
+        for suggested in moves {
+            let location = Location::from_json(&suggested["location"])?;
+            if let Some(inputs) = inputs(game, &location) {
+                // Holding with nothing held uses up the next piece as well.
+                let used = if inputs.first() == Some(&Action::Hold) && game.hold().is_none() {
+                    2
+                } else {
+                    1
+                };
+                for action in inputs {
+                    game.apply(action).ok();
+                }
+                self.send(&json!({"type": "play", "move": suggested}))?;
+                self.known -= used;
+                self.synced_at = Some(game.pieces + 1);
+                return Ok(());
+            }
+        }
+        // None of the moves can be made, as when the piece spawned into the
+        // stack. Dropping it lets the game go on or end as it would for a
+        // player, and the bot starts over from the position that follows.
+        game.apply(Action::HardDrop).ok();
+        self.synced_at = Some(game.pieces + 1);
+        self.stale = true;
+        Ok(())
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.send(&json!({"type": "quit"})).ok();
+        if let Some(child) = &mut self.child {
+            let deadline = Instant::now() + QUIT_GRACE;
+            while let Ok(None) = child.try_wait() {
+                if Instant::now() >= deadline {
+                    child.kill().ok();
+                    child.wait().ok();
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+}
+
+/// The inputs that bring the active piece to the location, holding it first
+/// if the location is for the piece hold would bring in.
+fn inputs(game: &Game, location: &Location) -> Option<Vec<Action>> {
+    let piece = game.piece();
+    let (piece, mut inputs) = if location.shape == piece.shape {
+        (piece.clone(), vec![])
+    } else {
+        if game.hold_used || game.puzzle.is_some() {
+            return None;
+        }
+        let piece = match game.hold() {
+            Some(held) => {
+                let rotation = held.get_possible_rotations()[0];
+                Tetromino::from_shape(*game.board(), 1, held, rotation)
+            }
+            None => game.next_tetromino.clone(),
+        };
+        (piece, vec![Action::Hold])
+    };
+    if piece.shape != location.shape {
+        return None;
+    }
+    let cells = location.cells();
+    let placement = placements(&Tetromino {
+        grid: *game.board(),
+        ..piece
+    })
+    .into_iter()
+    .find(|placement| placement.cells == cells)?;
+    inputs.extend(placement.inputs);
+    Some(inputs)
+}
+
+/// Answers a frontend on `input` and `output` on behalf of a bot that
+/// suggests moves with `suggest`, until the frontend quits.
+pub fn serve(
+    mut input: impl BufRead,
+    mut output: impl Write,
+    name: &str,
+    mut suggest: impl FnMut(&Position) -> Vec<Location>,
+) -> Result<(), String> {
+    send(
+        &mut output,
+        &json!({
+            "type": "info",
+            "name": name,
+            "version": env!("CARGO_PKG_VERSION"),
+            "author": env!("CARGO_PKG_AUTHORS"),
+            "features": [],
+        }),
+    )?;
+    let mut position = None;
+    loop {
+        let message = match receive(&mut input)? {
+            Some(message) => message,
+            None => return Ok(()),
+        };
+        match (message["type"].as_str(), &mut position) {
+            (Some("rules"), _) => send(&mut output, &json!({"type": "ready"}))?,
+            (Some("start"), _) => position = Some(Position::from_start(&message)?),
+            (Some("suggest"), Some(position)) => {
+                let moves = suggest(position)
+                    .into_iter()
+                    .map(|location| json!({"location": location.to_json(), "spin": "none"}))
+                    .collect::<Vec<Value>>();
+                send(&mut output, &json!({"type": "suggestion", "moves": moves}))?;
+            }
+            (Some("play"), Some(position)) => {
+                position.play(&Location::from_json(&message["move"]["location"])?)?
+            }
+            (Some("new_piece"), Some(position)) => {
+                position.queue.push_back(parse_shape(&message["piece"])?)
+            }
+            (Some("stop"), _) => position = None,
+            (Some("quit"), _) => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+/// The engine's AI as a TBP bot: suggests the placement `Bot` picks for the
+/// first piece in the queue.
+pub fn suggest(bot: &Bot, position: &Position) -> Vec<Location> {
+    let shape = match position.queue.front() {
+        Some(&shape) => shape,
+        None => return vec![],
+    };
+    let rotation = shape.get_possible_rotations()[0];
+    let piece = Tetromino::from_shape(position.board, 1, shape, rotation);
+    let queue = position
+        .queue
+        .iter()
+        .skip(1)
+        .copied()
+        .collect::<Vec<Shape>>();
+    bot.best_placement_on(&position.board, &piece, &queue)
+        .and_then(|placement| Location::from_cells(shape, &placement.cells))
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::Settings;
+    use std::sync::mpsc;
+
+    /// Where the piece lands when hard dropped from where it spawns.
+    fn drop_location(board: Grid, shape: Shape) -> Location {
+        let piece = Tetromino::from_shape(board, 1, shape, shape.get_possible_rotations()[0]);
+        let placement = placements(&piece)
+            .into_iter()
+            .find(|placement| placement.inputs == vec![Action::HardDrop])
+            .unwrap();
+        Location::from_cells(shape, &placement.cells).unwrap()
+    }
+
+    fn values(grid: &Grid) -> Vec<Vec<u8>> {
+        grid.iter()
+            .map(|row| row.iter().map(|block| block.value).collect())
+            .collect()
+    }
+
+    /// Plays `pieces` pieces with a bot served on another thread, checking
+    /// that the bot sees the same board as the game every time it is asked.
+    fn play_over_pipes(
+        pieces: u32,
+        mut suggest: impl FnMut(&Position) -> Vec<Location> + Send + 'static,
+    ) -> Game {
+        let (from_frontend, to_bot) = std::io::pipe().unwrap();
+        let (from_bot, to_frontend) = std::io::pipe().unwrap();
+        let (boards, seen) = mpsc::channel();
+        std::thread::spawn(move || {
+            serve(
+                BufReader::new(from_frontend),
+                to_frontend,
+                "stand-in",
+                |position| {
+                    boards.send(values(&position.board)).unwrap();
+                    suggest(position)
+                },
+            )
+        });
+
+        let mut client = Client::connect(BufReader::new(from_bot), to_bot).unwrap();
+        assert_eq!(client.name, "stand-in");
+        let mut game = Game::with_seed(Settings::default(), 1);
+        while game.pieces < pieces && !game.is_over() {
+            client.step(&mut game).unwrap();
+            if let Ok(board) = seen.try_recv() {
+                assert_eq!(board, values(game.board()));
+            }
+            game.tick();
+        }
+        game
+    }
+
+    #[test]
+    fn stuck_bot_is_killed() {
+        let (from_frontend, to_bot) = std::io::pipe().unwrap();
+        let (from_bot, to_frontend) = std::io::pipe().unwrap();
+        std::thread::spawn(move || {
+            serve(
+                BufReader::new(from_frontend),
+                to_frontend,
+                "stand-in",
+                |_| vec![],
+            )
+        });
+        let mut client = Client::connect(BufReader::new(from_bot), to_bot).unwrap();
+        // A bot that pays no attention to `quit`.
+        client.child = Some(Command::new("sleep").arg("60").spawn().unwrap());
+        let started = Instant::now();
+        drop(client);
+        assert!(started.elapsed() < QUIT_GRACE * 5);
+    }
+
+    #[test]
+    fn serve_ends() {
+        let serve = |input: &[u8]| serve(input, std::io::sink(), "stand-in", |_| vec![]);
+        assert_eq!(serve(b""), Ok(()));
+        assert_eq!(serve(b"{\"type\": \"rules\"}\n"), Ok(()));
+        assert_eq!(serve(b"{\"type\": \"quit\"}\nnot json\n"), Ok(()));
+        assert!(serve(b"not json\n")
+            .unwrap_err()
+            .starts_with("invalid message: "));
+    }
+
+    #[test]
+    fn suggested_where_it_rests() {
+        let (from_frontend, to_bot) = std::io::pipe().unwrap();
+        let (from_bot, to_frontend) = std::io::pipe().unwrap();
+        std::thread::spawn(move || {
+            serve(
+                BufReader::new(from_frontend),
+                to_frontend,
+                "stand-in",
+                |position| {
+                    let shape = position.queue[0];
+                    let rotation = shape.get_possible_rotations()[0];
+                    let piece = Tetromino::from_shape(position.board, 1, shape, rotation);
+                    vec![Location::from_cells(shape, &piece.cells()).unwrap()]
+                },
+            )
+        });
+        let mut client = Client::connect(BufReader::new(from_bot), to_bot).unwrap();
+
+        // The stack reaches up to just under where the piece spawns.
+        let mut game = Game::with_seed(Settings::default(), 1);
+        let shape = game.piece().shape;
+        let rotation = shape.get_possible_rotations()[0];
+        let piece = Tetromino::from_shape(Game::create_grid(), 1, shape, rotation);
+        let bottom = piece.cells().iter().map(|cell| cell.y).max().unwrap();
+        let mut board = Game::create_grid();
+        for row in board[bottom as usize + 1..].iter_mut() {
+            for block in row[1..].iter_mut() {
+                *block = Block::new(1, Some(Color::Gray));
+            }
+        }
+        game.set_position(board, piece.clone(), vec![], None);
+
+        assert_eq!(client.step(&mut game), Ok(()));
+        assert_eq!(game.piece().cells(), piece.cells());
+    }
+
+    #[test]
+    fn location_cells() {
+        let location = Location {
+            shape: Shape::T,
+            orientation: Orientation::East,
+            x: 4,
+            y: 1,
+        };
+        let cells = [(13, 4), (14, 4), (14, 5), (15, 4)]
+            .iter()
+            .map(|&(y, x)| Coord { y, x })
+            .collect::<Vec<Coord>>();
+        assert_eq!(location.cells(), cells);
+        assert_eq!(Location::from_cells(Shape::T, &cells), Some(location));
+        assert_eq!(Location::from_json(&location.to_json()), Ok(location));
+    }
+
+    #[test]
+    fn every_placement_has_a_location() {
+        for shape in Shape::iter() {
+            for rotation in shape.get_possible_rotations() {
+                let piece = Tetromino::from_shape(Game::create_grid(), 1, shape, rotation);
+                for placement in placements(&piece) {
+                    let location = Location::from_cells(shape, &placement.cells).unwrap();
+                    assert_eq!(location.cells(), placement.cells);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn start_round_trip() {
+        let mut game = Game::with_seed(Settings::default(), 1);
+        let mut board = Game::create_grid();
+        board[15][0] = Block::new(1, Some(Shape::L.get_color()));
+        board[14][9] = Block::new(1, Some(Shape::I.get_color()));
+        let piece = game.piece().clone();
+        game.set_position(board, piece, vec![], Some(Shape::O));
+        game.add_garbage(1, 3);
+        let position = Position::from_game(&game);
+        assert_eq!(position.queue.len(), 2);
+
+        let message = position.start_message();
+        assert_eq!(message["board"].as_array().unwrap().len(), BOARD_HEIGHT);
+        assert_eq!(message["board"][0][0], "G");
+        assert_eq!(message["board"][0][3], Value::Null);
+        assert_eq!(message["board"][1][0], "L");
+        assert_eq!(message["board"][2][9], "I");
+        assert_eq!(Position::from_start(&message), Ok(position));
+    }
+
+    #[test]
+    fn position_play() {
+        let mut position = Position {
+            board: Game::create_grid(),
+            queue: VecDeque::from(vec![Shape::I, Shape::T, Shape::O, Shape::S]),
+            hold: None,
+        };
+        position
+            .play(&drop_location(position.board, Shape::T))
+            .unwrap();
+        assert_eq!(position.hold, Some(Shape::I));
+        assert_eq!(position.queue, VecDeque::from(vec![Shape::O, Shape::S]));
+        position
+            .play(&drop_location(position.board, Shape::I))
+            .unwrap();
+        assert_eq!(position.hold, Some(Shape::O));
+        assert_eq!(position.queue, VecDeque::from(vec![Shape::S]));
+        assert!(position
+            .play(&drop_location(position.board, Shape::T))
+            .is_err());
+    }
+
+    #[test]
+    fn engine_bot_over_pipes() {
+        let bot = Bot::new(1, None);
+        let game = play_over_pipes(30, move |position| suggest(&bot, position));
+        assert_eq!(game.pieces, 30);
+        assert!(game.lines > 5);
+    }
+
+    #[test]
+    fn unreachable_moves_drop_the_piece() {
+        let game = play_over_pipes(4, |position| {
+            // Floating in the air above an empty board.
+            let mut location = drop_location(position.board, position.queue[0]);
+            location.y += 5;
+            vec![location]
+        });
+        assert_eq!(game.pieces, 4);
+    }
+
+    #[test]
+    fn stand_in_bot_holds() {
+        let game = play_over_pipes(4, |position| {
+            let swapped = position.hold.unwrap_or(position.queue[1]);
+            vec![drop_location(position.board, swapped)]
+        });
+        assert_eq!(game.pieces, 4);
+        assert!(game.hold().is_some());
+    }
+}
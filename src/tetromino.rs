@@ -2,57 +2,130 @@ use crate::core::{Coord, Direction, Grid, PLAYGROUND_HEIGHT, PLAYGROUND_WIDTH};
 use crate::shape::{Rotation, Shape};
 use crate::ui::Color;
 use rand::prelude::SliceRandom;
+use rand::Rng;
 
 #[cfg(test)]
 use rstest_reuse::{self, *};
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tetromino {
     pub grid: Grid,
     pub shape: Shape,
     pub color: Color,
     pub topleft: Coord,
     pub current_rotation: Rotation,
+    /// Every mino covers `scale` x `scale` cells of the grid.
+    pub scale: i32,
 }
 
 impl Tetromino {
-    pub fn new(grid: Grid) -> Tetromino {
-        let shape = rand::random::<Shape>();
-        let current_rotation = shape
-            .get_possible_rotations()
-            .choose(&mut rand::thread_rng())
-            .copied()
-            .unwrap();
+    pub fn new<R: Rng + ?Sized>(grid: Grid, scale: i32, rng: &mut R) -> Tetromino {
+        let shape = rng.gen::<Shape>();
+        let current_rotation = shape.get_possible_rotations().choose(rng).copied().unwrap();
+        Tetromino::from_shape(grid, scale, shape, current_rotation)
+    }
+
+    pub fn from_shape(
+        grid: Grid,
+        scale: i32,
+        shape: Shape,
+        current_rotation: Rotation,
+    ) -> Tetromino {
         let color = shape.get_color();
         Tetromino {
             grid,
             shape,
             color,
             current_rotation,
-            topleft: Coord {
-                y: 0,
-                x: PLAYGROUND_WIDTH / 2 - 1,
-            },
+            topleft: Tetromino::spawn_position(scale),
+            scale,
         }
     }
 
-    pub fn move_sideways(&mut self, direction: Direction) -> Result<(), &'static str> {
+    pub fn spawn_position(scale: i32) -> Coord {
+        Coord {
+            y: 0,
+            x: PLAYGROUND_WIDTH / 2 - 2 * scale + 1,
+        }
+    }
+
+    /// The grid cells covered by the tetromino.
+    pub fn cells(&self) -> Vec<Coord> {
+        self.cells_at(self.topleft, self.current_rotation)
+    }
+
+    /// How many of the four cells diagonal to the piece's center are filled
+    /// or out of bounds. Three or more after a rotation make a T-spin.
+    /// The center is the mino with three neighbours, so only T pieces have one.
+    pub fn filled_corners(&self) -> usize {
         let tetrovec = self.shape.to_4x4(self.current_rotation);
-        for (rowidx, row) in tetrovec.into_iter().enumerate() {
-            for (colidx, column) in row.into_iter().enumerate() {
-                if column != 0 {
-                    let Coord { y, x } = self.topleft;
-                    let next_step = colidx as i32 + x + direction as i32;
-                    if !(0..PLAYGROUND_WIDTH).contains(&next_step) {
-                        return Err("Out of bounds.");
-                    }
-                    if self.grid[rowidx + y as usize][next_step as usize].value != 0 {
-                        return Err("Collision.");
-                    }
+        let filled = |row: i32, col: i32| {
+            (0..4).contains(&row)
+                && (0..4).contains(&col)
+                && tetrovec[row as usize][col as usize] != 0
+        };
+        let center = (0..16).map(|i| (i / 4, i % 4)).find(|&(row, col)| {
+            filled(row, col)
+                && [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                    .iter()
+                    .filter(|(dy, dx)| filled(row + dy, col + dx))
+                    .count()
+                    == 3
+        });
+        let (row, col) = match center {
+            Some(center) => center,
+            None => return 0,
+        };
+
+        [(-1, -1), (-1, 1), (1, -1), (1, 1)]
+            .iter()
+            .filter(|(dy, dx)| {
+                let y = self.topleft.y + (row + dy) * self.scale;
+                let x = self.topleft.x + (col + dx) * self.scale;
+                !(0..PLAYGROUND_WIDTH).contains(&x)
+                    || y >= PLAYGROUND_HEIGHT
+                    || (y >= 0 && self.grid[y as usize][x as usize].value != 0)
+            })
+            .count()
+    }
+
+    fn cells_at(&self, topleft: Coord, rotation: Rotation) -> Vec<Coord> {
+        let mut cells = Vec::with_capacity(4 * (self.scale * self.scale) as usize);
+        // Reads the bits directly rather than through `to_4x4`, as this runs
+        // for every position the piece is checked at.
+        for i in (0..16).filter(|i| rotation >> (15 - i) & 1 != 0) {
+            for dy in 0..self.scale {
+                for dx in 0..self.scale {
+                    cells.push(Coord {
+                        y: topleft.y + i / 4 * self.scale + dy,
+                        x: topleft.x + i % 4 * self.scale + dx,
+                    });
                 }
             }
         }
-        self.topleft.x += direction as i32;
+        cells
+    }
+
+    pub(crate) fn check(&self, topleft: Coord, rotation: Rotation) -> Result<(), &'static str> {
+        for Coord { y, x } in self.cells_at(topleft, rotation) {
+            if !(0..PLAYGROUND_WIDTH).contains(&x) || y >= PLAYGROUND_HEIGHT {
+                return Err("Out of bounds.");
+            }
+            if y >= 0 && self.grid[y as usize][x as usize].value != 0 {
+                return Err("Collision.");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn move_sideways(&mut self, direction: Direction) -> Result<(), &'static str> {
+        let topleft = Coord {
+            y: self.topleft.y,
+            x: self.topleft.x + direction as i32 * self.scale,
+        };
+        self.check(topleft, self.current_rotation)?;
+        self.topleft = topleft;
 
         Ok(())
     }
@@ -63,62 +136,39 @@ impl Tetromino {
         }
     }
 
+    pub fn is_grounded(&self) -> bool {
+        self.clone().move_down().is_err()
+    }
+
     pub fn move_down(&mut self) -> Result<(), &'static str> {
-        let tetrovec = self.shape.to_4x4(self.current_rotation);
-        for (rowidx, row) in tetrovec.into_iter().enumerate() {
-            for (colidx, column) in row.into_iter().enumerate() {
-                if column != 0 {
-                    let Coord { y, x } = self.topleft;
-                    let next_step = Coord {
-                        y: rowidx as i32 + y + 1,
-                        x: colidx as i32 + x,
-                    };
-                    if next_step.y >= PLAYGROUND_HEIGHT {
-                        return Err("Out of bounds.");
-                    }
-                    if self.grid[next_step.y as usize][next_step.x as usize].value != 0 {
-                        return Err("Collision.");
-                    }
-                }
-            }
-        }
-        self.topleft.y += 1;
+        let topleft = Coord {
+            y: self.topleft.y + self.scale,
+            x: self.topleft.x,
+        };
+        self.check(topleft, self.current_rotation)?;
+        self.topleft = topleft;
 
         Ok(())
     }
 
     pub fn rotate(&mut self, direction: Direction) -> Result<(), &'static str> {
+        self.rotate_by(direction as i32)
+    }
+
+    pub fn rotate_180(&mut self) -> Result<(), &'static str> {
+        self.rotate_by(2)
+    }
+
+    fn rotate_by(&mut self, turns: i32) -> Result<(), &'static str> {
         let rotations = self.shape.get_possible_rotations();
         let current_index = rotations
             .iter()
             .position(|x| *x == self.current_rotation)
             .unwrap();
-        let next_index = i32::checked_rem_euclid(
-            current_index as i32 + direction as i32,
-            rotations.len() as i32,
-        );
+        let next_index =
+            i32::checked_rem_euclid(current_index as i32 + turns, rotations.len() as i32);
         let potential_rotation = rotations[next_index.unwrap() as usize];
-        let tetrovec = self.shape.to_4x4(potential_rotation);
-        for (rowidx, row) in tetrovec.into_iter().enumerate() {
-            for (colidx, column) in row.into_iter().enumerate() {
-                if column != 0 {
-                    let Coord { y, x } = self.topleft;
-                    let next_step = Coord {
-                        y: rowidx as i32 + y,
-                        x: colidx as i32 + x,
-                    };
-                    if !(0..PLAYGROUND_WIDTH).contains(&next_step.x) {
-                        return Err("Out of bounds.");
-                    }
-                    if next_step.y >= PLAYGROUND_HEIGHT {
-                        return Err("Out of bounds.");
-                    }
-                    if self.grid[next_step.y as usize][next_step.x as usize].value != 0 {
-                        return Err("Collision.");
-                    }
-                }
-            }
-        }
+        self.check(self.topleft, potential_rotation)?;
         self.current_rotation = potential_rotation;
         Ok(())
     }
@@ -133,7 +183,7 @@ mod tests {
     #[fixture]
     fn tetromino() -> Tetromino {
         let grid = Game::create_grid();
-        let mut tetromino = Tetromino::new(grid);
+        let mut tetromino = Tetromino::new(grid, 1, &mut rand::thread_rng());
         tetromino.topleft = Coord { y: 5, x: 5 };
         tetromino
     }
@@ -238,6 +288,14 @@ mod tests {
         assert_eq!(tetromino.move_down(), Err("Collision."));
     }
 
+    #[apply(all_shapes)]
+    fn is_grounded(mut tetromino: Tetromino, shape: Shape) {
+        tetromino.shape = shape;
+        assert!(!tetromino.is_grounded());
+        tetromino.move_all_the_way_down();
+        assert!(tetromino.is_grounded());
+    }
+
     #[apply(all_shapes)]
     fn rotate_left_ok(mut tetromino: Tetromino, shape: Shape) {
         tetromino.shape = shape;
@@ -258,15 +316,25 @@ mod tests {
         let possible_rotations = tetromino.shape.get_possible_rotations();
         tetromino.current_rotation = possible_rotations[0];
 
-        for rotation_index in 1..possible_rotations.len() {
+        for rotation in possible_rotations.iter().skip(1) {
             assert_eq!(tetromino.rotate(Direction::Right), Ok(()));
-            assert_eq!(
-                tetromino.current_rotation,
-                possible_rotations[rotation_index]
-            )
+            assert_eq!(tetromino.current_rotation, *rotation)
         }
     }
 
+    #[apply(all_shapes)]
+    fn rotate_180_ok(mut tetromino: Tetromino, shape: Shape) {
+        tetromino.shape = shape;
+        let possible_rotations = tetromino.shape.get_possible_rotations();
+        tetromino.current_rotation = possible_rotations[0];
+        let mut twice = tetromino.clone();
+        twice.rotate(Direction::Right).unwrap();
+        twice.rotate(Direction::Right).unwrap();
+
+        assert_eq!(tetromino.rotate_180(), Ok(()));
+        assert_eq!(tetromino.current_rotation, twice.current_rotation);
+    }
+
     #[apply(all_shapes)]
     fn rotate_left_out_of_bounds(mut tetromino: Tetromino, shape: Shape) {
         tetromino.shape = shape;
@@ -276,7 +344,7 @@ mod tests {
         for rotation in possible_rotations {
             tetromino.current_rotation = rotation;
             assert_eq!(tetromino.rotate(Direction::Left), Err("Out of bounds."));
-            assert_eq!(tetromino.current_rotation, tetromino.current_rotation);
+            assert_eq!(tetromino.current_rotation, rotation);
         }
     }
 
@@ -289,7 +357,7 @@ mod tests {
         for rotation in possible_rotations {
             tetromino.current_rotation = rotation;
             assert_eq!(tetromino.rotate(Direction::Right), Err("Out of bounds."));
-            assert_eq!(tetromino.current_rotation, tetromino.current_rotation);
+            assert_eq!(tetromino.current_rotation, rotation);
         }
     }
 
@@ -305,7 +373,7 @@ mod tests {
         for rotation in possible_rotations {
             tetromino.current_rotation = rotation;
             assert_eq!(tetromino.rotate(Direction::Left), Err("Collision."));
-            assert_eq!(tetromino.current_rotation, tetromino.current_rotation);
+            assert_eq!(tetromino.current_rotation, rotation);
         }
     }
 
@@ -321,7 +389,56 @@ mod tests {
         for rotation in possible_rotations {
             tetromino.current_rotation = rotation;
             assert_eq!(tetromino.rotate(Direction::Right), Err("Collision."));
-            assert_eq!(tetromino.current_rotation, tetromino.current_rotation);
+            assert_eq!(tetromino.current_rotation, rotation);
         }
     }
+
+    #[apply(all_shapes)]
+    fn big_cells(mut tetromino: Tetromino, shape: Shape) {
+        tetromino.shape = shape;
+        tetromino.current_rotation = tetromino.shape.get_possible_rotations()[0];
+        tetromino.scale = 2;
+        tetromino.topleft = Coord { y: 0, x: 0 };
+        let cells = tetromino.cells();
+        assert_eq!(cells.len(), 16);
+        assert!(cells.iter().all(|cell| cell.y < 8 && cell.x < 8));
+    }
+
+    #[apply(all_shapes)]
+    fn big_moves_by_two(mut tetromino: Tetromino, shape: Shape) {
+        tetromino.shape = shape;
+        tetromino.current_rotation = tetromino.shape.get_possible_rotations()[0];
+        tetromino.scale = 2;
+        tetromino.topleft = Coord { y: 0, x: 2 };
+        assert_eq!(tetromino.move_down(), Ok(()));
+        assert_eq!(tetromino.topleft.y, 2);
+        assert_eq!(tetromino.move_sideways(Direction::Left), Ok(()));
+        assert_eq!(tetromino.topleft.x, 0);
+    }
+
+    #[apply(all_shapes)]
+    fn big_spawn_fits(shape: Shape) {
+        let mut tetromino = Tetromino::new(Game::create_grid(), 2, &mut rand::thread_rng());
+        tetromino.shape = shape;
+        for rotation in tetromino.shape.get_possible_rotations() {
+            tetromino.current_rotation = rotation;
+            assert!(tetromino
+                .cells()
+                .iter()
+                .all(|cell| (0..PLAYGROUND_WIDTH).contains(&cell.x)));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[rstest]
+    fn serde_round_trip(tetromino: Tetromino) {
+        let json = serde_json::to_string(&tetromino).unwrap();
+        let loaded = serde_json::from_str::<Tetromino>(&json).unwrap();
+        assert_eq!(loaded.grid, tetromino.grid);
+        assert_eq!(loaded.shape, tetromino.shape);
+        assert_eq!(loaded.color, tetromino.color);
+        assert_eq!(loaded.topleft, tetromino.topleft);
+        assert_eq!(loaded.current_rotation, tetromino.current_rotation);
+        assert_eq!(loaded.scale, tetromino.scale);
+    }
 }
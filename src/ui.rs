@@ -1,4 +1,11 @@
-use crate::core::{Coord, Grid, PLAYGROUND_HEIGHT, PLAYGROUND_WIDTH};
+use crate::core::{
+    Action, Coord, Game, Grid, FRAMES_PER_SECOND, PLAYGROUND_HEIGHT, PLAYGROUND_WIDTH,
+};
+use crate::finesse::Finesse;
+use crate::movegen::Placement;
+use crate::opener::Status;
+use crate::shape::Shape;
+use crate::stats::{self, Stats};
 use crate::tetromino::Tetromino;
 use ncurses as nc;
 use strum::IntoEnumIterator;
@@ -6,6 +13,8 @@ use strum_macros::EnumIter;
 
 const SCREEN_WIDTH: i32 = PLAYGROUND_WIDTH * 2;
 const SCREEN_HEIGHT: i32 = PLAYGROUND_HEIGHT;
+/// Outlines are drawn in the color of their pair's number minus this.
+const OUTLINE_PAIR: i16 = 8;
 
 pub fn curses_init() {
     nc::setlocale(nc::LcCategory::all, "");
@@ -28,26 +37,38 @@ pub fn init_color_pairs() {
     nc::start_color();
     nc::init_color(nc::COLOR_YELLOW, 1000, 1000, 0);
     Color::iter().for_each(|color| {
-        nc::init_pair(color as i16, color as i16, color as i16);
+        nc::init_pair(color as i16, color.shade(), color.shade());
+        nc::init_pair(OUTLINE_PAIR + color as i16, color.shade(), nc::COLOR_BLACK);
     });
 }
 
-pub fn create_screens() -> (nc::WINDOW, nc::WINDOW) {
-    let border_screen = nc::subwin(
-        nc::stdscr(),
-        1 + SCREEN_HEIGHT + 1,
-        1 + SCREEN_WIDTH + 1,
-        (nc::LINES() - SCREEN_HEIGHT) / 2 - 1,
-        (nc::COLS() - SCREEN_WIDTH) / 2 - 1,
-    );
-    let inner_screen = nc::subwin(
-        nc::stdscr(),
-        SCREEN_HEIGHT,
-        SCREEN_WIDTH,
-        (nc::LINES() - SCREEN_HEIGHT) / 2,
-        (nc::COLS() - SCREEN_WIDTH) / 2,
-    );
-    (border_screen, inner_screen)
+/// Lays out a playfield for each board, side by side with room for their
+/// HUDs, and returns the border and inner screen of each, left to right.
+pub fn create_screens(boards: i32) -> Vec<(nc::WINDOW, nc::WINDOW)> {
+    let y = (nc::LINES() - SCREEN_HEIGHT) / 2;
+    (0..boards)
+        .map(|board| {
+            // Each board is centered in its share of the terminal's width.
+            let x = nc::COLS() * (2 * board + 1) / (2 * boards) - SCREEN_WIDTH / 2;
+            let border_screen = nc::subwin(
+                nc::stdscr(),
+                1 + SCREEN_HEIGHT + 1,
+                1 + SCREEN_WIDTH + 1,
+                y - 1,
+                x - 1,
+            );
+            let inner_screen = nc::subwin(nc::stdscr(), SCREEN_HEIGHT, SCREEN_WIDTH, y, x);
+            (border_screen, inner_screen)
+        })
+        .collect()
+}
+
+/// Where the playfield drawn on the inner screen starts, to place its HUD
+/// around it.
+fn origin(screen: nc::WINDOW) -> (i32, i32) {
+    let (mut y, mut x) = (0, 0);
+    nc::getbegyx(screen, &mut y, &mut x);
+    (y, x)
 }
 
 pub fn erase_screens(border_screen: nc::WINDOW, inner_screen: nc::WINDOW) {
@@ -61,46 +82,143 @@ pub fn refresh_screens(inner_screen: nc::WINDOW) {
     nc::wrefresh(inner_screen);
 }
 
+/// Draws the playfield and the HUD, with the outline of the hinted placement
+/// if there is one, without refreshing the screens.
+pub fn draw_game(
+    game: &Game,
+    hint: Option<&Placement>,
+    border_screen: nc::WINDOW,
+    inner_screen: nc::WINDOW,
+) {
+    erase_screens(inner_screen, border_screen);
+    draw_board(game, hint, border_screen, inner_screen);
+}
+
+/// Draws the playfield and the HUD like `draw_game`, over what is already
+/// drawn on the other boards.
+pub fn draw_board(
+    game: &Game,
+    hint: Option<&Placement>,
+    border_screen: nc::WINDOW,
+    inner_screen: nc::WINDOW,
+) {
+    nc::box_(border_screen, 0, 0);
+
+    draw_landed_tetrominos(inner_screen, &game.visible_grid());
+    if let Some(placement) = hint {
+        draw_outline(inner_screen, &placement.cells, placement.shape.get_color());
+    }
+    if game.entry_delay == 0 && !game.is_over() {
+        draw_tetromino(inner_screen, game.piece());
+    }
+    if game.has_next() {
+        draw_next_tetromino(inner_screen, &game.next_tetromino);
+    }
+    if let Some(shape) = game.hold() {
+        draw_hold(inner_screen, shape);
+    }
+    draw_score(inner_screen, game.score());
+    if !game.is_high_score_eligible() {
+        draw_practice(inner_screen);
+    }
+    if let Some(puzzle) = &game.puzzle {
+        let pieces_left = puzzle.queue.len() - game.pieces as usize;
+        draw_goal(inner_screen, &puzzle.goal.to_string(), pieces_left);
+    }
+    if let Some(grade) = game.settings.mode.grade(game.score()) {
+        draw_grade(inner_screen, grade, game.level);
+        draw_section_times(inner_screen, &game.section_times, game.section_time());
+    }
+}
+
 pub fn draw_tetromino(screen: nc::WINDOW, tetromino: &Tetromino) {
+    draw_cells(screen, &tetromino.cells(), tetromino.color);
+}
+
+pub fn draw_cells(screen: nc::WINDOW, cells: &[Coord], color: Color) {
+    nc::wattr_on(screen, nc::COLOR_PAIR(color as i16));
+    for Coord { y, x } in cells {
+        nc::mvwaddstr(screen, *y, x * 2, "██");
+    }
+    nc::wattroff(screen, nc::COLOR_PAIR(color as i16));
+}
+
+/// Draws the outline of where a piece would go.
+pub fn draw_outline(screen: nc::WINDOW, cells: &[Coord], color: Color) {
+    let pair = nc::COLOR_PAIR(OUTLINE_PAIR + color as i16);
+    nc::wattr_on(screen, pair);
+    for Coord { y, x } in cells {
+        nc::mvwaddstr(screen, *y, x * 2, "[]");
+    }
+    nc::wattroff(screen, pair);
+}
+
+/// Draws the cells of an opener's template that the stack has yet to fill,
+/// faintly, around the active piece.
+pub fn draw_template(screen: nc::WINDOW, template: &Grid, game: &Game) {
+    let piece = game.piece().cells();
+    for (rowidx, row) in template.iter().enumerate() {
+        for (colidx, block) in row.iter().enumerate() {
+            let cell = Coord {
+                y: rowidx as i32,
+                x: colidx as i32,
+            };
+            let covered = game.board()[rowidx][colidx].value != 0
+                || (game.entry_delay == 0 && piece.contains(&cell));
+            if block.value == 0 || covered {
+                continue;
+            }
+            let attributes =
+                nc::COLOR_PAIR(OUTLINE_PAIR + block.color.unwrap() as i16) | nc::A_DIM();
+            nc::wattr_on(screen, attributes);
+            nc::mvwaddstr(screen, cell.y, cell.x * 2, "··");
+            nc::wattroff(screen, attributes);
+        }
+    }
+}
+
+pub fn draw_next_tetromino(screen: nc::WINDOW, tetromino: &Tetromino) {
+    let (y, x) = origin(screen);
+
+    nc::mvaddstr(y, x + SCREEN_WIDTH + 6, "NEXT");
+
     let tetrovec = tetromino.shape.to_4x4(tetromino.current_rotation);
+
     for (rowidx, row) in tetrovec.into_iter().enumerate() {
         for (colidx, column) in row.into_iter().enumerate() {
             if column != 0 {
-                let Coord { y, x } = tetromino.topleft;
-                nc::wattr_on(screen, nc::COLOR_PAIR(tetromino.color as i16));
-                nc::mvwaddstr(
-                    screen,
-                    rowidx as i32 + y as i32,
-                    (colidx as i32 + x as i32) * 2,
+                nc::attron(nc::COLOR_PAIR(tetromino.color as i16));
+                nc::mvaddstr(
+                    rowidx as i32 + y + 2,
+                    (colidx as i32 * 2) + x + SCREEN_WIDTH + 2,
                     "██",
                 );
-                nc::wattroff(screen, nc::COLOR_PAIR(tetromino.color as i16));
+                nc::attroff(nc::COLOR_PAIR(tetromino.color as i16));
             }
         }
     }
 }
 
-pub fn draw_next_tetromino(tetromino: &Tetromino) {
-    let y = (nc::LINES() - SCREEN_HEIGHT) / 2;
-    let x = (nc::COLS() - SCREEN_WIDTH) / 2;
-
-    nc::mvaddstr(y, x + SCREEN_WIDTH + 6, "NEXT");
+pub fn draw_hold(screen: nc::WINDOW, shape: Shape) {
+    let (y, x) = origin(screen);
 
-    let tetrovec = tetromino.shape.to_4x4(tetromino.current_rotation);
+    nc::mvaddstr(y + 7, x + SCREEN_WIDTH + 6, "HOLD");
 
+    let color = shape.get_color();
+    let tetrovec = shape.to_4x4(shape.get_possible_rotations()[0]);
+    nc::attron(nc::COLOR_PAIR(color as i16));
     for (rowidx, row) in tetrovec.into_iter().enumerate() {
         for (colidx, column) in row.into_iter().enumerate() {
             if column != 0 {
-                nc::attron(nc::COLOR_PAIR(tetromino.color as i16));
                 nc::mvaddstr(
-                    rowidx as i32 + y as i32 + 2,
+                    rowidx as i32 + y + 9,
                     (colidx as i32 * 2) + x + SCREEN_WIDTH + 2,
                     "██",
                 );
-                nc::attroff(nc::COLOR_PAIR(tetromino.color as i16));
             }
         }
     }
+    nc::attroff(nc::COLOR_PAIR(color as i16));
 }
 
 pub fn draw_landed_tetrominos(screen: nc::WINDOW, grid: &Grid) {
@@ -115,13 +233,232 @@ pub fn draw_landed_tetrominos(screen: nc::WINDOW, grid: &Grid) {
     }
 }
 
-pub fn draw_score(score: u64) {
-    let y = (nc::LINES() - SCREEN_HEIGHT) / 2 + SCREEN_HEIGHT + 1;
-    let x = (nc::COLS() - SCREEN_WIDTH) / 2 - 1;
+pub fn draw_score(screen: nc::WINDOW, score: u64) {
+    let (top, left) = origin(screen);
+    let y = top + SCREEN_HEIGHT + 1;
+    let x = left - 1;
     nc::mvwaddstr(nc::stdscr(), y, x, &format!("SCORE: {}", score));
 }
 
+pub fn draw_playback(status: &str, pressed: &[Action]) {
+    let y = (nc::LINES() - SCREEN_HEIGHT) / 2 - 3;
+    let x = (nc::COLS() - SCREEN_WIDTH) / 2 - 1;
+    nc::mvaddstr(y, x, status);
+
+    let mut x = x;
+    for action in Action::iter() {
+        let label = match action {
+            Action::MoveLeft => "←",
+            Action::MoveRight => "→",
+            Action::SoftDrop => "↓",
+            Action::HardDrop => "DROP",
+            Action::RotateLeft => "↺",
+            Action::RotateRight => "↻",
+            Action::Rotate180 => "180",
+            Action::Hold => "HOLD",
+            Action::Undo => "UNDO",
+            Action::Redo => "REDO",
+        };
+        if pressed.contains(&action) {
+            nc::attron(nc::A_REVERSE());
+        }
+        nc::mvaddstr(y + 1, x, label);
+        nc::attroff(nc::A_REVERSE());
+        x += label.chars().count() as i32 + 1;
+    }
+}
+
+/// Draws the running statistics in a column right of the next and held
+/// pieces.
+pub fn draw_stats(stats: &Stats) {
+    let y = (nc::LINES() - SCREEN_HEIGHT) / 2;
+    let x = (nc::COLS() - SCREEN_WIDTH) / 2 + SCREEN_WIDTH + 16;
+    let lines = [
+        format!("TIME   {}", format_time(stats.frames)),
+        format!("PIECES {}", stats.pieces),
+        format!("PPS    {:.2}", stats.pps()),
+        format!("KPP    {:.2}", stats.kpp()),
+        format!("LINES  {}", stats.lines),
+        format!("APM    {:.1}", stats.apm()),
+        String::new(),
+        format!("SINGLE {}", stats.singles),
+        format!("DOUBLE {}", stats.doubles),
+        format!("TRIPLE {}", stats.triples),
+        format!("TETRIS {}", stats.tetrises),
+        format!("T-SPIN {}", stats.t_spins),
+    ];
+    for (i, line) in lines.iter().enumerate() {
+        nc::mvaddstr(y + i as i32, x, line);
+    }
+}
+
+/// Draws how many of each shape have spawned, each next to a small picture of
+/// it in its color, and how long it has been since the last I piece, in a
+/// column far left of the playfield.
+pub fn draw_distribution(stats: &Stats) {
+    let y = (nc::LINES() - SCREEN_HEIGHT) / 2;
+    let x = (nc::COLS() - SCREEN_WIDTH) / 2 - 30;
+    for (idx, shape) in stats::SHAPES.iter().enumerate() {
+        let y = y + idx as i32 * 2;
+        // The flattest rotation, which fits in two rows.
+        let rotation = shape
+            .get_possible_rotations()
+            .into_iter()
+            .min_by_key(|&rotation| {
+                shape
+                    .to_4x4(rotation)
+                    .iter()
+                    .filter(|row| row.contains(&1))
+                    .count()
+            })
+            .unwrap();
+        let rows = shape
+            .to_4x4(rotation)
+            .into_iter()
+            .filter(|row| row.contains(&1))
+            .collect::<Vec<Vec<u16>>>();
+        let left = (0..4)
+            .find(|&col| rows.iter().any(|row| row[col] == 1))
+            .unwrap();
+        nc::attron(nc::COLOR_PAIR(shape.get_color() as i16));
+        for (rowidx, row) in rows.iter().enumerate() {
+            for (colidx, &column) in row.iter().enumerate().skip(left) {
+                if column != 0 {
+                    nc::mvaddstr(y + rowidx as i32, x + (colidx - left) as i32, "█");
+                }
+            }
+        }
+        nc::attroff(nc::COLOR_PAIR(shape.get_color() as i16));
+        let count = stats.spawned.get(shape).unwrap_or(&0);
+        nc::mvaddstr(y, x + 6, &format!("{:03}", count));
+    }
+    nc::mvaddstr(y + 15, x, &format!("DROUGHT {}", stats.drought));
+}
+
+/// Draws the lines describing a reviewed placement above the playfield.
+pub fn draw_review(lines: &[String]) {
+    let y = (nc::LINES() - SCREEN_HEIGHT) / 2 - 1 - lines.len() as i32;
+    let x = (nc::COLS() - SCREEN_WIDTH) / 2 - 1;
+    for (i, line) in lines.iter().enumerate() {
+        nc::mvaddstr(y + i as i32, x, line);
+    }
+}
+
+/// Draws the finesse faults so far and how many presses the last placement
+/// wasted, with the presses turned down in drills.
+pub fn draw_finesse(finesse: &Finesse, rejected: Option<u32>) {
+    let y = (nc::LINES() - SCREEN_HEIGHT) / 2 + SCREEN_HEIGHT + 3;
+    let x = (nc::COLS() - SCREEN_WIDTH) / 2 - 1;
+    let mut text = format!("FAULTS: {}/{}", finesse.faults(), finesse.judgements.len());
+    if let Some(last) = finesse.judgements.last().filter(|last| last.is_fault()) {
+        text += &format!("  LAST +{}", last.wasted());
+    }
+    if let Some(rejected) = rejected {
+        text += &format!("  REJECTED: {}", rejected);
+    }
+    nc::mvaddstr(y, x, &text);
+}
+
+/// Draws how far the stack is into the opener, left of the playfield.
+pub fn draw_opener(name: &str, status: &Status, built: bool) {
+    let y = (nc::LINES() - SCREEN_HEIGHT) / 2 + SCREEN_HEIGHT - 6;
+    let x = (nc::COLS() - SCREEN_WIDTH) / 2 - 16;
+    nc::mvaddstr(y, x, "OPENER");
+    nc::mvaddstr(y + 2, x, &name.to_uppercase());
+    if built {
+        nc::mvaddstr(y + 3, x, "BUILT");
+        return;
+    }
+    nc::mvaddstr(y + 3, x, &format!("{}/{}", status.filled, status.total));
+    let note = if !status.is_on_track() {
+        "OFF TEMPLATE"
+    } else if status.mirrored {
+        "MIRRORED"
+    } else {
+        ""
+    };
+    nc::mvaddstr(y + 5, x, note);
+}
+
+/// Draws the player's name above the playfield and the garbage waiting to
+/// rise as a meter along its left side, with the count below it once it
+/// does not fit.
+pub fn draw_versus(screen: nc::WINDOW, player: &str, pending: u32) {
+    let (top, left) = origin(screen);
+    nc::mvaddstr(top - 2, left - 1, player);
+    let shown = (pending as i32).min(SCREEN_HEIGHT);
+    nc::attron(nc::COLOR_PAIR(Color::Red as i16));
+    for row in 0..shown {
+        nc::mvaddstr(top + SCREEN_HEIGHT - 1 - row, left - 3, "█");
+    }
+    nc::attroff(nc::COLOR_PAIR(Color::Red as i16));
+    if pending as i32 > SCREEN_HEIGHT {
+        nc::mvaddstr(top + SCREEN_HEIGHT + 1, left - 4, &pending.to_string());
+    }
+}
+
+pub fn draw_practice(screen: nc::WINDOW) {
+    let (top, left) = origin(screen);
+    let y = top + SCREEN_HEIGHT + 2;
+    let x = left - 1;
+    nc::mvwaddstr(nc::stdscr(), y, x, "PRACTICE: NO HIGH SCORE");
+}
+
+pub fn draw_game_over(screen: nc::WINDOW, message: &str) {
+    let (top, left) = origin(screen);
+    let y = top + SCREEN_HEIGHT + 1;
+    let x = left + SCREEN_WIDTH + 1 - message.len() as i32;
+    nc::mvwaddstr(nc::stdscr(), y, x, message);
+}
+
+pub fn draw_menu(screen: nc::WINDOW, title: &str, items: &[String], selected: usize) {
+    nc::mvwaddstr(screen, 1, 1, title);
+    for (idx, item) in items.iter().enumerate() {
+        if idx == selected {
+            nc::wattr_on(screen, nc::A_REVERSE());
+        }
+        nc::mvwaddstr(screen, 3 + idx as i32, 1, item);
+        nc::wattroff(screen, nc::A_REVERSE());
+    }
+}
+
+pub fn draw_goal(screen: nc::WINDOW, goal: &str, pieces_left: usize) {
+    let (y, left) = origin(screen);
+    let x = left - 16;
+    nc::mvaddstr(y, x, "GOAL");
+    nc::mvaddstr(y + 2, x, goal);
+    nc::mvaddstr(y + 4, x, &format!("PIECES: {}", pieces_left));
+}
+
+pub fn draw_grade(screen: nc::WINDOW, grade: &str, level: u32) {
+    let (y, x) = origin(screen);
+    nc::mvaddstr(y + 14, x + SCREEN_WIDTH + 4, &format!("GRADE: {}", grade));
+    nc::mvaddstr(y + 16, x + SCREEN_WIDTH + 4, &format!("LEVEL: {}", level));
+}
+
+pub fn draw_section_times(screen: nc::WINDOW, section_times: &[u64], current: u64) {
+    let (y, left) = origin(screen);
+    let x = left - 14;
+    nc::mvaddstr(y, x, "SECTIONS");
+    for (idx, time) in section_times.iter().chain(Some(&current)).enumerate() {
+        nc::mvaddstr(y + 2 + idx as i32, x, &format_time(*time));
+    }
+}
+
+fn format_time(frames: u64) -> String {
+    let fps = FRAMES_PER_SECOND as u64;
+    let seconds = frames / fps;
+    let centiseconds = frames % fps * 100 / fps;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 60,
+        seconds % 60,
+        centiseconds
+    )
+}
+
 #[derive(Clone, Copy, Debug, EnumIter, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Color {
     Yellow = nc::COLOR_YELLOW as isize,
     Blue = nc::COLOR_BLUE as isize,
@@ -130,4 +467,18 @@ pub enum Color {
     Magenta = nc::COLOR_MAGENTA as isize,
     Cyan = nc::COLOR_CYAN as isize,
     White = nc::COLOR_WHITE as isize,
+    /// Garbage, which belongs to no shape.
+    Gray = 8,
+}
+
+impl Color {
+    /// The terminal color to draw in, white standing in for gray on terminals
+    /// with only eight colors.
+    fn shade(self) -> i16 {
+        if self == Color::Gray && nc::COLORS() <= Color::Gray as i32 {
+            nc::COLOR_WHITE
+        } else {
+            self as i16
+        }
+    }
 }
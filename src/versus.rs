@@ -0,0 +1,308 @@
+use crate::core::{Game, PLAYGROUND_WIDTH};
+use crate::event::{ClearType, Event};
+use crate::mode::Settings;
+use crate::random::Random;
+use rand::Rng;
+
+/// How many pieces per second the bot places in versus unless told.
+pub const DEFAULT_BOT_PPS: f64 = 1.0;
+
+/// How many lines of garbage each kind of clear sends, read from lines such
+/// as:
+///
+/// ```text
+/// tetris: 4
+/// t-spin double: 4
+/// perfect clear: 10
+/// ```
+///
+/// Clears left out send what they do by default.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AttackTable {
+    pub single: u32,
+    pub double: u32,
+    pub triple: u32,
+    pub tetris: u32,
+    pub t_spin_single: u32,
+    pub t_spin_double: u32,
+    pub t_spin_triple: u32,
+    /// Sent on top of the clear that emptied the board.
+    pub perfect_clear: u32,
+}
+
+impl Default for AttackTable {
+    fn default() -> AttackTable {
+        AttackTable {
+            single: ClearType::Single.attack(),
+            double: ClearType::Double.attack(),
+            triple: ClearType::Triple.attack(),
+            tetris: ClearType::Tetris.attack(),
+            t_spin_single: ClearType::TSpinSingle.attack(),
+            t_spin_double: ClearType::TSpinDouble.attack(),
+            t_spin_triple: ClearType::TSpinTriple.attack(),
+            perfect_clear: 10,
+        }
+    }
+}
+
+impl AttackTable {
+    pub fn load(path: &str) -> Result<AttackTable, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        AttackTable::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(text: &str) -> Result<AttackTable, String> {
+        let mut table = AttackTable::default();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (key, value) = line
+                .split_once(':')
+                .ok_or(format!("expected `key: value`, got: {}", line))?;
+            let value = value.trim();
+            let lines = value
+                .parse()
+                .map_err(|_| format!("invalid line count: {}", value))?;
+            match key.trim() {
+                "single" => table.single = lines,
+                "double" => table.double = lines,
+                "triple" => table.triple = lines,
+                "tetris" => table.tetris = lines,
+                "t-spin single" => table.t_spin_single = lines,
+                "t-spin double" => table.t_spin_double = lines,
+                "t-spin triple" => table.t_spin_triple = lines,
+                "perfect clear" => table.perfect_clear = lines,
+                key => return Err(format!("unknown clear: {}", key)),
+            }
+        }
+        Ok(table)
+    }
+
+    pub fn attack(&self, clear: ClearType) -> u32 {
+        match clear {
+            ClearType::Single => self.single,
+            ClearType::Double => self.double,
+            ClearType::Triple => self.triple,
+            ClearType::Tetris => self.tetris,
+            ClearType::TSpinSingle => self.t_spin_single,
+            ClearType::TSpinDouble => self.t_spin_double,
+            ClearType::TSpinTriple => self.t_spin_triple,
+        }
+    }
+}
+
+/// Two games played side by side on the same piece sequence, where line
+/// clears send garbage to the other board. Garbage first cancels what is
+/// pending against the sender, and the rest rises on the opponent's board
+/// when their next piece locks. The first to top out loses.
+pub struct Versus {
+    pub games: [Game; 2],
+    /// Lines of garbage waiting to rise on each board.
+    pub pending: [u32; 2],
+    pub table: AttackTable,
+    /// Picks the column of the hole in each batch of garbage.
+    rng: Random,
+    /// Whether each player's piece has locked since their garbage last rose.
+    locked: [bool; 2],
+}
+
+impl Versus {
+    pub fn new(settings: Settings, seed: u64, table: AttackTable) -> Versus {
+        Versus {
+            games: [
+                Game::with_seed(settings, seed),
+                Game::with_seed(settings, seed),
+            ],
+            pending: [0, 0],
+            table,
+            rng: Random::new(seed),
+            locked: [false, false],
+        }
+    }
+
+    /// Advances both games by one frame and exchanges the garbage sent, and
+    /// returns what happened in each game.
+    pub fn tick(&mut self) -> [Vec<Event>; 2] {
+        let events = [0, 1].map(|player| {
+            let game = &mut self.games[player];
+            if !game.is_over() {
+                game.tick();
+            }
+            game.drain_events()
+        });
+        for (player, events) in events.iter().enumerate() {
+            for event in events {
+                self.observe(player, event);
+            }
+        }
+        events
+    }
+
+    fn observe(&mut self, player: usize, event: &Event) {
+        match event {
+            Event::LinesCleared { clear, .. } => {
+                let mut attack = self.table.attack(*clear);
+                if *self.games[player].board() == Game::create_grid() {
+                    attack += self.table.perfect_clear;
+                }
+                let cancelled = attack.min(self.pending[player]);
+                self.pending[player] -= cancelled;
+                self.pending[1 - player] += attack - cancelled;
+            }
+            Event::Locked { .. } => self.locked[player] = true,
+            // The garbage rises between one piece locking and the next
+            // spawning, so the piece that cleared lines gets to cancel it.
+            Event::Spawned { .. } if self.locked[player] => {
+                self.locked[player] = false;
+                let lines = std::mem::take(&mut self.pending[player]);
+                if lines > 0 {
+                    let hole = self.rng.gen_range(0..PLAYGROUND_WIDTH as usize);
+                    self.games[player].add_garbage(lines, hole);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.games.iter().any(|game| game.is_over())
+    }
+
+    /// The player still standing once the other has topped out, or `None`
+    /// while both are playing or if both went down on the same frame.
+    pub fn winner(&self) -> Option<usize> {
+        match (self.games[0].is_over(), self.games[1].is_over()) {
+            (false, true) => Some(0),
+            (true, false) => Some(1),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Action, Block, Coord};
+    use crate::shape::Shape;
+    use crate::tetromino::Tetromino;
+    use crate::ui::Color;
+    use crate::{puzzle, save};
+    use rstest::rstest;
+
+    /// Makes the active piece a flat I and fills the bottom row around where
+    /// it lands, so dropping it clears the row and empties the board.
+    fn set_up_clear(versus: &mut Versus, player: usize) {
+        let mut piece = Tetromino::from_shape(Game::create_grid(), 1, Shape::I, 240);
+        piece.move_all_the_way_down();
+        let cells = piece.cells();
+        let mut grid = Game::create_grid();
+        for (x, block) in grid[15].iter_mut().enumerate() {
+            if !cells.contains(&Coord { y: 15, x: x as i32 }) {
+                *block = Block::new(1, Some(Color::White));
+            }
+        }
+        let piece = Tetromino::from_shape(grid, 1, Shape::I, 240);
+        versus.games[player].set_position(grid, piece, vec![], None);
+    }
+
+    /// Every clear sends three lines, perfect clears no more.
+    fn threes() -> AttackTable {
+        AttackTable {
+            single: 3,
+            double: 3,
+            triple: 3,
+            tetris: 3,
+            t_spin_single: 3,
+            t_spin_double: 3,
+            t_spin_triple: 3,
+            perfect_clear: 0,
+        }
+    }
+
+    fn drop(versus: &mut Versus, player: usize) {
+        versus.games[player].apply(Action::HardDrop).unwrap();
+        let pieces = versus.games[player].pieces;
+        while versus.games[player].pieces == pieces {
+            versus.tick();
+        }
+    }
+
+    #[rstest(
+        text,
+        expected,
+        case("", Ok(AttackTable::default())),
+        case(
+            "single: 1\nperfect clear: 6\n",
+            Ok(AttackTable { single: 1, perfect_clear: 6, ..AttackTable::default() })
+        ),
+        case("quad: 4", Err("unknown clear: quad".to_string())),
+        case("tetris: many", Err("invalid line count: many".to_string())),
+        case("tetris", Err("expected `key: value`, got: tetris".to_string()))
+    )]
+    fn parse_attack_table(text: &str, expected: Result<AttackTable, String>) {
+        assert_eq!(AttackTable::parse(text), expected);
+    }
+
+    #[test]
+    fn clears_send_garbage() {
+        let mut versus = Versus::new(Settings::default(), 1, threes());
+        set_up_clear(&mut versus, 0);
+        drop(&mut versus, 0);
+        assert_eq!(versus.pending, [0, 3]);
+
+        // The garbage rises once player 2 locks a piece.
+        drop(&mut versus, 1);
+        assert_eq!(versus.pending, [0, 0]);
+        for row in &versus.games[1].board()[13..] {
+            assert_eq!(row.iter().filter(|block| block.value == 0).count(), 1);
+        }
+        assert!(!versus.is_over());
+    }
+
+    #[test]
+    fn clears_cancel_pending_garbage() {
+        let mut versus = Versus::new(Settings::default(), 1, threes());
+        versus.pending = [2, 0];
+        set_up_clear(&mut versus, 0);
+        drop(&mut versus, 0);
+        assert_eq!(versus.pending, [0, 1]);
+        assert_eq!(*versus.games[0].board(), Game::create_grid());
+    }
+
+    #[test]
+    fn perfect_clear_bonus() {
+        let table = AttackTable {
+            perfect_clear: 10,
+            ..threes()
+        };
+        let mut versus = Versus::new(Settings::default(), 1, table);
+        set_up_clear(&mut versus, 1);
+        drop(&mut versus, 1);
+        assert_eq!(versus.pending, [13, 0]);
+    }
+
+    #[test]
+    fn garbage_stays_garbage() {
+        let mut versus = Versus::new(Settings::default(), 1, AttackTable::default());
+        versus.pending = [0, 2];
+        drop(&mut versus, 1);
+        let game = &versus.games[1];
+        for row in &game.board()[14..] {
+            let cells = row.iter().map(puzzle::format_cell).collect::<String>();
+            assert_eq!(cells.matches('#').count(), PLAYGROUND_WIDTH as usize - 1);
+        }
+        assert_eq!(
+            save::parse(&save::format(game)).unwrap().board(),
+            game.board()
+        );
+    }
+
+    #[test]
+    fn first_to_top_out_loses() {
+        let mut versus = Versus::new(Settings::default(), 1, AttackTable::default());
+        assert_eq!(versus.winner(), None);
+        versus.pending = [0, 20];
+        drop(&mut versus, 1);
+        assert!(versus.games[1].is_over());
+        assert!(versus.is_over());
+        assert_eq!(versus.winner(), Some(0));
+    }
+}
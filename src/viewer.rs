@@ -0,0 +1,115 @@
+use ncurses as nc;
+use std::time::{Duration, Instant};
+use tetris_rs::core::{Action, Game, FRAMES_PER_SECOND};
+use tetris_rs::replay::Replay;
+use tetris_rs::ui;
+
+const SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const NORMAL_SPEED: usize = 2;
+/// How long a key stays lit in the input overlay.
+const INPUT_OVERLAY_FRAMES: u64 = 8;
+
+const KEY_SPACE: i32 = b' ' as i32;
+const KEY_PLUS: i32 = b'+' as i32;
+const KEY_MINUS: i32 = b'-' as i32;
+const KEY_DOT: i32 = b'.' as i32;
+const KEY_G: i32 = b'g' as i32;
+const KEY_BACKSPACE: i32 = 127;
+const ENTER: i32 = b'\n' as i32;
+const ESC: i32 = 27;
+
+/// Plays a replay back with pause, speed control, frame stepping and
+/// jumping to a piece number.
+///
+/// - space: pause and resume
+/// - `+`, `-`: play faster or slower, from 0.25x to 8x
+/// - `.`: step one frame while paused
+/// - `g`: type a piece number and press enter to jump to it
+/// - ESC: quit
+pub fn run(replay: &Replay, border_screen: nc::WINDOW, inner_screen: nc::WINDOW) {
+    let frame_duration = Duration::from_secs(1) / FRAMES_PER_SECOND;
+    nc::wtimeout(inner_screen, frame_duration.as_millis() as i32);
+
+    let mut game = replay.new_game();
+    let mut playing = true;
+    let mut paused = false;
+    let mut speed = NORMAL_SPEED;
+    let mut goto: Option<String> = None;
+    let mut next_frame = Instant::now();
+
+    loop {
+        ui::draw_game(&game, None, border_screen, inner_screen);
+        let pressed = replay
+            .recent_inputs(game.frame, INPUT_OVERLAY_FRAMES)
+            .iter()
+            .map(|input| input.action)
+            .collect::<Vec<Action>>();
+        ui::draw_playback(&status(replay, &game, speed, paused, &goto), &pressed);
+        ui::refresh_screens(inner_screen);
+
+        let user_input = nc::wgetch(inner_screen);
+        if let Some(number) = &mut goto {
+            match user_input {
+                ENTER | nc::KEY_ENTER => {
+                    if let Ok(piece) = number.parse() {
+                        (game, playing) = replay.seek(piece);
+                    }
+                    goto = None;
+                }
+                ESC => goto = None,
+                KEY_BACKSPACE | nc::KEY_BACKSPACE => {
+                    number.pop();
+                }
+                _ => {
+                    if let Some(digit) =
+                        char::from_u32(user_input as u32).filter(char::is_ascii_digit)
+                    {
+                        number.push(digit);
+                    }
+                }
+            }
+            next_frame = Instant::now();
+            continue;
+        }
+
+        match user_input {
+            ESC => return,
+            KEY_SPACE => paused = !paused,
+            KEY_PLUS => speed = (speed + 1).min(SPEEDS.len() - 1),
+            KEY_MINUS => speed = speed.saturating_sub(1),
+            KEY_DOT if paused && playing => playing = replay.step(&mut game),
+            KEY_G => goto = Some(String::new()),
+            _ => {}
+        }
+
+        if paused {
+            next_frame = Instant::now();
+            continue;
+        }
+        let step_duration = frame_duration.div_f64(SPEEDS[speed]);
+        while playing && next_frame <= Instant::now() {
+            playing = replay.step(&mut game);
+            next_frame += step_duration;
+        }
+    }
+}
+
+fn status(
+    replay: &Replay,
+    game: &Game,
+    speed: usize,
+    paused: bool,
+    goto: &Option<String>,
+) -> String {
+    if let Some(number) = goto {
+        return format!("GO TO PIECE: {}_", number);
+    }
+    format!(
+        "REPLAY {}x  FRAME {}/{}  PIECE {}{}",
+        SPEEDS[speed],
+        game.frame,
+        replay.frames,
+        game.pieces + 1,
+        if paused { "  PAUSED" } else { "" }
+    )
+}
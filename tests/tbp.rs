@@ -0,0 +1,33 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tetris_rs::ai::Player;
+use tetris_rs::core::Game;
+use tetris_rs::mode::Settings;
+use tetris_rs::tbp::Client;
+
+const TETRIS_RS: &str = env!("CARGO_BIN_EXE_tetris-rs");
+
+#[test]
+fn plays_against_the_tbp_subcommand() {
+    let mut client = Client::spawn(&format!("{} tbp --depth 1", TETRIS_RS)).unwrap();
+    assert_eq!(client.name, "tetris-rs");
+    let mut game = Game::with_seed(Settings::default(), 1);
+    while game.pieces < 10 && !game.is_over() {
+        client.step(&mut game).unwrap();
+        game.tick();
+    }
+    assert_eq!(game.pieces, 10);
+}
+
+#[test]
+fn tbp_subcommand_fails_on_a_malformed_message() {
+    let mut bot = Command::new(TETRIS_RS)
+        .arg("tbp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    writeln!(bot.stdin.take().unwrap(), "not json").unwrap();
+    assert!(!bot.wait().unwrap().success());
+}